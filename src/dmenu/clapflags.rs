@@ -1,9 +1,14 @@
 use clap::{App, ArgMatches};
 use itertools::Itertools;
-use regex::RegexBuilder;
+use libc::c_int;
+use std::{env, ffi::OsString, fs, process};
 use yaml_rust::yaml::Yaml;
 
-use crate::config::{Clrs::*, Config, DefaultWidth, Schemes::*};
+use crate::config::{
+    Align, Anchor, Clrs::*, Config, DefaultWidth, Edge::*, FieldSpec, GridOrder, Length, MatchMode,
+    OutputFormat, Schemes::*, Styling,
+};
+use crate::config_file;
 use crate::result::*;
 
 lazy_static::lazy_static! {
@@ -13,7 +18,22 @@ lazy_static::lazy_static! {
             .pop()
             .unwrap()
         };
-    pub static ref CLAP_FLAGS: ArgMatches<'static> = App::from_yaml(&YAML).get_matches();
+    pub static ref CLAP_FLAGS: ArgMatches<'static> = {
+        let app = App::from_yaml(&YAML);
+        let cli = app.clone().get_matches();
+        // the config file goes before the real arguments, which are left out of it
+        let mut args: Vec<OsString> = env::args_os().collect();
+        match config_file::args(&YAML, &cli) {
+            Ok(settings) => {
+                args.splice(1..1, settings);
+            }
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            }
+        }
+        app.get_matches_from(args)
+    };
 }
 
 pub fn validate(config: &mut Config) -> CompResult<()> {
@@ -46,16 +66,58 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
         }
     }
 
-    let color_regex = RegexBuilder::new("^#([A-Fa-f0-9]{6}|[A-Fa-f0-9]{3})\0$")
-        .case_insensitive(true)
-        .build()
-        .map_err(|_| Die::Stderr("Could not build regex".to_owned()))?;
-
     // bottom
     if CLAP_FLAGS.occurrences_of("bottom") == 1 {
         config.topbar = false;
     }
 
+    // x_offset
+    if let Some(x) = length("x_offset")? {
+        config.x_offset = x;
+    }
+
+    // y_offset
+    if let Some(y) = length("y_offset")? {
+        config.y_offset = y;
+    }
+
+    // width
+    if let Some(width) = length("width")? {
+        match width {
+            Length::Pixels(w) if w <= 0 => {
+                return Die::stderr("--width: Width must be positive".to_owned())
+            }
+            Length::Percent(p) if p <= 0.0 => {
+                return Die::stderr("--width: Width must be positive".to_owned())
+            }
+            _ => config.width = Some(width),
+        }
+    }
+
+    // anchor
+    if let Some(anchor) = CLAP_FLAGS.value_of("anchor") {
+        config.anchor = Some(match anchor {
+            "top-left" => Anchor(Start, Start),
+            "top" => Anchor(Middle, Start),
+            "top-right" => Anchor(End, Start),
+            "left" => Anchor(Start, Middle),
+            "center" => Anchor(Middle, Middle),
+            "right" => Anchor(End, Middle),
+            "bottom-left" => Anchor(Start, End),
+            "bottom" => Anchor(Middle, End),
+            "bottom-right" => Anchor(End, End),
+            _ => return Die::stderr("--anchor: invalid arguement".to_owned()),
+        });
+    }
+
+    // center
+    if CLAP_FLAGS.occurrences_of("center") == 1 {
+        config.anchor = Some(Anchor(Middle, Middle));
+        if config.width.is_none() {
+            config.width = Some(Length::Percent(50.0));
+        }
+    }
+
     // fast
     if CLAP_FLAGS.occurrences_of("fast") == 1 {
         config.fast = true;
@@ -73,6 +135,23 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
             .map_err(|_| Die::Stderr("-l: Lines must be a non-negaitve integer".to_owned()))?;
     }
 
+    // columns
+    if let Some(columns) = CLAP_FLAGS.value_of("columns") {
+        config.columns = match columns.parse::<u32>() {
+            Ok(columns) if columns > 0 => columns,
+            _ => return Die::stderr("-g: Columns must be a positive integer".to_owned()),
+        };
+    }
+
+    // grid_order
+    if let Some(order) = CLAP_FLAGS.value_of("grid_order") {
+        config.grid_order = match order {
+            "column" => GridOrder::Columns,
+            "row" => GridOrder::Rows,
+            _ => return Die::stderr("--grid_order: invalid arguement".to_owned()),
+        };
+    }
+
     // monitor
     if let Some(monitor) = CLAP_FLAGS.value_of("monitor") {
         config.mon = monitor
@@ -94,46 +173,130 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
 
     // color_normal_background
     if let Some(color) = CLAP_FLAGS.value_of("color_normal_background") {
-        let mut color = color.to_string();
-        color.push('\0');
-        color_regex.find_iter(&color).nth(0).ok_or(Die::Stderr(
-            "--nb: Color must be in hex format (#123456 or #123)".to_owned(),
-        ))?;
-        config.colors[SchemeNorm as usize][ColBg as usize][..color.len()]
-            .copy_from_slice(color.as_bytes());
+        config.colors[SchemeNorm as usize][ColBg as usize] = color.to_owned();
     }
 
     // color_normal_foreground
     if let Some(color) = CLAP_FLAGS.value_of("color_normal_foreground") {
-        let mut color = color.to_string();
-        color.push('\0');
-        color_regex.find_iter(&color).nth(0).ok_or(Die::Stderr(
-            "--nf: Color must be in hex format (#123456 or #123)".to_owned(),
-        ))?;
-        config.colors[SchemeNorm as usize][ColFg as usize][..color.len()]
-            .copy_from_slice(color.as_bytes());
+        config.colors[SchemeNorm as usize][ColFg as usize] = color.to_owned();
     }
 
     // color_selected_background
     if let Some(color) = CLAP_FLAGS.value_of("color_selected_background") {
-        let mut color = color.to_string();
-        color.push('\0');
-        color_regex.find_iter(&color).nth(0).ok_or(Die::Stderr(
-            "--sb: Color must be in hex format (#123456 or #123)".to_owned(),
-        ))?;
-        config.colors[SchemeSel as usize][ColBg as usize][..color.len()]
-            .copy_from_slice(color.as_bytes());
+        config.colors[SchemeSel as usize][ColBg as usize] = color.to_owned();
     }
 
     // color_selected_foreground
     if let Some(color) = CLAP_FLAGS.value_of("color_selected_foreground") {
-        let mut color = color.to_string();
-        color.push('\0');
-        color_regex.find_iter(&color).nth(0).ok_or(Die::Stderr(
-            "--sf: Color must be in hex format (#123456 or #123)".to_owned(),
-        ))?;
-        config.colors[SchemeSel as usize][ColFg as usize][..color.len()]
-            .copy_from_slice(color.as_bytes());
+        config.colors[SchemeSel as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // color_highlight_background
+    if let Some(color) = CLAP_FLAGS.value_of("color_highlight_background") {
+        config.colors[SchemeHighlight as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // color_highlight_foreground
+    if let Some(color) = CLAP_FLAGS.value_of("color_highlight_foreground") {
+        config.colors[SchemeHighlight as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // color_out_background
+    if let Some(color) = CLAP_FLAGS.value_of("color_out_background") {
+        config.colors[SchemeOut as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // color_out_foreground
+    if let Some(color) = CLAP_FLAGS.value_of("color_out_foreground") {
+        config.colors[SchemeOut as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // color_prompt_background
+    if let Some(color) = CLAP_FLAGS.value_of("color_prompt_background") {
+        config.colors[SchemePrompt as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // color_prompt_foreground
+    if let Some(color) = CLAP_FLAGS.value_of("color_prompt_foreground") {
+        config.colors[SchemePrompt as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // border_color
+    if let Some(color) = CLAP_FLAGS.value_of("border_color") {
+        config.colors[SchemeBorder as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // color_counter_background
+    if let Some(color) = CLAP_FLAGS.value_of("color_counter_background") {
+        config.colors[SchemeCounter as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // color_counter_foreground
+    if let Some(color) = CLAP_FLAGS.value_of("color_counter_foreground") {
+        config.colors[SchemeCounter as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // scrollbar_color
+    if let Some(color) = CLAP_FLAGS.value_of("scrollbar_color") {
+        config.colors[SchemeScrollbar as usize][ColFg as usize] = color.to_owned();
+    }
+
+    // scrollbar_track_color
+    if let Some(color) = CLAP_FLAGS.value_of("scrollbar_track_color") {
+        config.colors[SchemeScrollbar as usize][ColBg as usize] = color.to_owned();
+    }
+
+    // border_width
+    if let Some(width) = CLAP_FLAGS.value_of("border_width") {
+        config.border_width = width.parse::<u32>().map_err(|_| {
+            Die::Stderr("--border_width: Width must be a non-negative integer".to_owned())
+        })?;
+    }
+
+    // argb
+    if CLAP_FLAGS.occurrences_of("argb") == 1 {
+        config.argb = true;
+    }
+
+    // keymap
+    if let Some(path) = CLAP_FLAGS.value_of("keymap") {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Die::Stderr(format!("--keymap: could not read {}: {}", path, e)))?;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            config
+                .keymap
+                .bind(line)
+                .map_err(|e| Die::Stderr(format!("--keymap: {} line {}: {}", path, i + 1, e)))?;
+        }
+    }
+
+    // bind
+    if let Some(bindings) = CLAP_FLAGS.values_of("bind") {
+        for binding in bindings {
+            config
+                .keymap
+                .bind(binding)
+                .map_err(|e| Die::Stderr(format!("--bind: {}", e)))?;
+        }
+    }
+
+    // counter
+    if CLAP_FLAGS.occurrences_of("counter") == 1 {
+        config.counter = true;
+    }
+
+    // scrollbar
+    if CLAP_FLAGS.occurrences_of("scrollbar") == 1 {
+        config.scrollbar = true;
+    }
+
+    // hover
+    if CLAP_FLAGS.occurrences_of("hover") == 1 {
+        config.hover = true;
     }
 
     // window
@@ -148,6 +311,121 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
         config.nostdin = true;
     }
 
+    // stream
+    if CLAP_FLAGS.occurrences_of("stream") == 1 {
+        config.stream = true;
+    }
+
+    // parallel_threshold
+    if let Some(threshold) = CLAP_FLAGS.value_of("parallel_threshold") {
+        config.parallel_threshold = threshold.parse::<usize>().map_err(|_| {
+            Die::Stderr("--parallel_threshold: Threshold must be a non-negative integer".to_owned())
+        })?;
+    }
+
+    // match
+    if let Some(mode) = CLAP_FLAGS.value_of("match") {
+        config.match_mode = match mode {
+            "substring" => MatchMode::Substring,
+            "regex" => MatchMode::Regex,
+            "glob" => MatchMode::Glob,
+            "prefix" => MatchMode::Prefix,
+            "exact" => MatchMode::Exact,
+            _ => return Die::stderr("--match: invalid arguement".to_owned()),
+        };
+    }
+
+    // history
+    if let Some(file) = CLAP_FLAGS.value_of("history") {
+        config.history = Some(file.to_owned());
+    }
+
+    // multi
+    if CLAP_FLAGS.occurrences_of("multi") == 1 {
+        config.multi = true;
+    }
+
+    // multi_limit
+    if let Some(limit) = CLAP_FLAGS.value_of("multi_limit") {
+        config.multi_limit = Some(limit.parse::<usize>().map_err(|_| {
+            Die::Stderr("--multi_limit: Limit must be a non-negative integer".to_owned())
+        })?);
+    }
+
+    // delimiter
+    if let Some(delimiter) = CLAP_FLAGS.value_of("delimiter") {
+        if delimiter.is_empty() {
+            return Die::stderr("--delimiter: Delimiter cannot be empty".to_owned());
+        }
+        config.delimiter = delimiter.to_owned();
+    }
+
+    // with_nth, nth, output_nth
+    if let Some(spec) = fields("with_nth")? {
+        config.with_nth = Some(spec);
+    }
+    if let Some(spec) = fields("nth")? {
+        config.nth = Some(spec);
+    }
+    if let Some(spec) = fields("output_nth")? {
+        config.output_nth = Some(spec);
+    }
+
+    // icons
+    if CLAP_FLAGS.occurrences_of("icons") == 1 {
+        config.icons = true;
+    }
+
+    // icon_theme
+    if let Some(theme) = CLAP_FLAGS.value_of("icon_theme") {
+        config.icon_theme = theme.to_owned();
+    }
+
+    // ansi, markup
+    if CLAP_FLAGS.occurrences_of("ansi") == 1 {
+        config.styling = Styling::Ansi;
+    }
+    if CLAP_FLAGS.occurrences_of("markup") == 1 {
+        config.styling = Styling::Markup;
+    }
+
+    // format
+    if let Some(format) = CLAP_FLAGS.value_of("format") {
+        config.format = match format {
+            "text" => OutputFormat::Text,
+            "index" => OutputFormat::Index,
+            "both" => OutputFormat::Both,
+            _ => return Die::stderr("--format: invalid arguement".to_owned()),
+        };
+    }
+
+    // index_sentinel
+    if let Some(sentinel) = CLAP_FLAGS.value_of("index_sentinel") {
+        config.index_sentinel = sentinel.to_owned();
+    }
+
+    // select
+    if let Some(text) = CLAP_FLAGS.value_of("select") {
+        config.select = Some(text.to_owned());
+    }
+
+    // select_index
+    if let Some(index) = CLAP_FLAGS.value_of("select_index") {
+        config.select_index = Some(index.parse::<usize>().map_err(|_| {
+            Die::Stderr("--select_index: Index must be a non-negative integer".to_owned())
+        })?);
+    }
+
+    // smart_case
+    if CLAP_FLAGS.occurrences_of("smart_case") == 1 {
+        config.smart_case = true;
+    }
+
+    // normalize
+    if CLAP_FLAGS.occurrences_of("normalize") == 1 {
+        config.normalize = true;
+    }
+
     // render_minheight
     if let Some(minheight) = CLAP_FLAGS.value_of("render_minheight") {
         config.render_minheight = minheight.parse::<u32>().map_err(|_| {
@@ -159,6 +437,36 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
         })?;
     }
 
+    // hpad, vpad, line_spacing
+    let pixels = |flag: &str| match CLAP_FLAGS.value_of(flag) {
+        Some(value) => value.parse::<u32>().map(Some).map_err(|_| {
+            Die::Stderr(format!(
+                "--{}: Padding must be a non-negative integer number of pixels",
+                flag
+            ))
+        }),
+        None => Ok(None),
+    };
+    if let Some(hpad) = pixels("hpad")? {
+        config.hpad = Some(hpad);
+    }
+    if let Some(vpad) = pixels("vpad")? {
+        config.vpad = vpad;
+    }
+    if let Some(spacing) = pixels("line_spacing")? {
+        config.line_spacing = spacing;
+    }
+
+    // align
+    if let Some(align) = CLAP_FLAGS.value_of("align") {
+        config.align = match align {
+            "left" => Align::Left,
+            "center" => Align::Center,
+            "right" => Align::Right,
+            _ => return Die::stderr("--align: invalid arguement".to_owned()),
+        };
+    }
+
     // render_overrun
     if CLAP_FLAGS.occurrences_of("render_overrun") == 1 {
         config.render_overrun = true;
@@ -210,3 +518,61 @@ pub fn validate(config: &mut Config) -> CompResult<()> {
 
     Ok(())
 }
+
+/// Parses a field list like `1,3..4,6..` given to `flag`, numbered from 1
+fn fields(flag: &str) -> CompResult<Option<FieldSpec>> {
+    let arg = match CLAP_FLAGS.value_of(flag) {
+        Some(arg) => arg,
+        None => return Ok(None),
+    };
+    let invalid = || {
+        Die::Stderr(format!(
+            "--{}: Fields must be comma seperated numbers or ranges like N, N.., ..M or N..M, \
+             counting from 1",
+            flag
+        ))
+    };
+    let number = |n: &str| match n.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(invalid()),
+    };
+    let mut ranges = Vec::new();
+    for field in arg.split(',') {
+        ranges.push(match field.find("..") {
+            None => number(field).map(|n| n..=n)?,
+            Some(split) => {
+                let (start, end) = (&field[..split], &field[split + 2..]);
+                let start = if start.is_empty() { 0 } else { number(start)? };
+                let end = if end.is_empty() {
+                    usize::MAX
+                } else {
+                    number(end)?
+                };
+                if start > end {
+                    return Err(invalid()); // would silently select nothing
+                }
+                start..=end
+            }
+        });
+    }
+    Ok(Some(FieldSpec(ranges)))
+}
+
+/// Pixels like `300`, or a percentage of the screen like `50%`
+fn length(flag: &str) -> CompResult<Option<Length>> {
+    let arg = match CLAP_FLAGS.value_of(flag) {
+        Some(arg) => arg,
+        None => return Ok(None),
+    };
+    let length = if let Some(percent) = arg.strip_suffix('%') {
+        percent.parse::<f32>().ok().map(Length::Percent)
+    } else {
+        arg.parse::<c_int>().ok().map(Length::Pixels)
+    };
+    length.map(Some).ok_or_else(|| {
+        Die::Stderr(format!(
+            "--{}: Must be a number of pixels, or a percentage like 50%",
+            flag
+        ))
+    })
+}
@@ -1,10 +1,18 @@
 use libc::{c_int, c_uint};
+use std::ops::RangeInclusive;
 use x11::xlib::Window;
 
+use crate::keys::Keymap;
+
 pub enum Schemes {
     SchemeNorm,
     SchemeSel,
     SchemeOut,
+    SchemeHighlight,
+    SchemePrompt,
+    SchemeBorder,
+    SchemeCounter,
+    SchemeScrollbar,
     SchemeLast,
 }
 pub enum Clrs {
@@ -22,10 +30,78 @@ pub enum DefaultWidth {
     Custom(u8),
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MatchMode {
+    Substring,
+    Regex,
+    Glob,
+    Prefix,
+    Exact,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Index,
+    Both,
+}
+
+/// Where item text sits in the space it has
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Styling taken out of each line of stdin
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Styling {
+    Plain,
+    Ansi,   // SGR escape sequences
+    Markup, // Pango-like tags
+}
+
+/// The order items fill a `--columns` grid in
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GridOrder {
+    Columns, // down each column, then across
+    Rows,    // across each row, then down
+}
+
+/// A size or position, either absolute or relative to the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(c_int),
+    Percent(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Start,
+    Middle,
+    End,
+}
+
+/// Where the window sits on its screen, as (horizontal, vertical)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor(pub Edge, pub Edge);
+
+/// Which fields of a delimited line to use, as 0 based indices
+#[derive(Debug, Clone)]
+pub struct FieldSpec(pub Vec<RangeInclusive<usize>>);
+
 #[derive(Debug)]
 pub struct Config {
     pub lines: c_uint,
+    pub columns: c_uint,
+    pub grid_order: GridOrder,
     pub topbar: bool,
+    pub anchor: Option<Anchor>,
+    pub x_offset: Length,
+    pub y_offset: Length,
+    pub width: Option<Length>,
+    pub border_width: u32,
     pub prompt: String,
     pub promptw: c_int,
     pub fontstrings: Vec<String>,
@@ -33,22 +109,75 @@ pub struct Config {
     pub embed: Window,
     pub case_sensitive: bool,
     pub mon: c_int,
-    pub colors: [[[u8; 8]; 2]; SchemeLast as usize],
+    pub colors: [[String; 2]; SchemeLast as usize],
+    pub argb: bool,
+    pub keymap: Keymap,
+    pub hover: bool,
+    pub counter: bool,
+    pub scrollbar: bool,
     pub render_minheight: u32,
+    pub hpad: Option<u32>, // half the font height if unset
+    pub vpad: u32,
+    pub line_spacing: u32,
+    pub align: Align,
     pub render_overrun: bool,
     pub render_flex: bool,
     pub render_rightalign: bool,
     pub render_default_width: DefaultWidth,
     pub nostdin: bool,
+    pub stream: bool,
+    pub parallel_threshold: usize,
+    pub match_mode: MatchMode,
+    pub history: Option<String>,
+    pub multi: bool,
+    pub multi_limit: Option<usize>,
+    pub delimiter: String,
+    pub with_nth: Option<FieldSpec>,
+    pub nth: Option<FieldSpec>,
+    pub output_nth: Option<FieldSpec>,
+    pub icons: bool,
+    pub icon_theme: String,
+    pub styling: Styling,
+    pub format: OutputFormat,
+    pub index_sentinel: String,
+    pub select: Option<String>,
+    pub select_index: Option<usize>,
+    pub smart_case: bool,
+    pub normalize: bool,
 }
 
 pub struct ConfigDefault {}
 
+impl Config {
+    /// `colors`, with those left unset (empty) taken from another scheme
+    pub fn resolved_colors(&self) -> [[String; 2]; SchemeLast as usize] {
+        let mut colors = self.colors.clone();
+        let mut inherit = |scheme: Schemes, from: Schemes| {
+            let from = colors[from as usize].clone();
+            for (color, fallback) in colors[scheme as usize].iter_mut().zip(from) {
+                if color.is_empty() {
+                    *color = fallback;
+                }
+            }
+        };
+        inherit(SchemeHighlight, SchemeNorm);
+        inherit(SchemePrompt, SchemeSel);
+        colors
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             lines: ConfigDefault::lines(),
+            columns: ConfigDefault::columns(),
+            grid_order: ConfigDefault::grid_order(),
             topbar: ConfigDefault::topbar(),
+            anchor: ConfigDefault::anchor(),
+            x_offset: ConfigDefault::x_offset(),
+            y_offset: ConfigDefault::y_offset(),
+            width: ConfigDefault::width(),
+            border_width: ConfigDefault::border_width(),
             prompt: ConfigDefault::prompt(),
             promptw: 0,
             fontstrings: ConfigDefault::fontstrings(),
@@ -57,12 +186,40 @@ impl Default for Config {
             case_sensitive: ConfigDefault::case_sensitive(),
             mon: ConfigDefault::mon(),
             colors: ConfigDefault::colors(),
+            argb: ConfigDefault::argb(),
+            keymap: ConfigDefault::keymap(),
+            hover: ConfigDefault::hover(),
+            counter: ConfigDefault::counter(),
+            scrollbar: ConfigDefault::scrollbar(),
             render_minheight: ConfigDefault::render_minheight(),
+            hpad: ConfigDefault::hpad(),
+            vpad: ConfigDefault::vpad(),
+            line_spacing: ConfigDefault::line_spacing(),
+            align: ConfigDefault::align(),
             render_overrun: ConfigDefault::render_overrun(),
             render_flex: ConfigDefault::render_flex(),
             render_rightalign: ConfigDefault::render_rightalign(),
             render_default_width: ConfigDefault::render_default_width(),
             nostdin: ConfigDefault::nostdin(),
+            stream: ConfigDefault::stream(),
+            parallel_threshold: ConfigDefault::parallel_threshold(),
+            match_mode: ConfigDefault::match_mode(),
+            history: ConfigDefault::history(),
+            multi: ConfigDefault::multi(),
+            multi_limit: ConfigDefault::multi_limit(),
+            delimiter: ConfigDefault::delimiter(),
+            with_nth: ConfigDefault::with_nth(),
+            nth: ConfigDefault::nth(),
+            output_nth: ConfigDefault::output_nth(),
+            icons: ConfigDefault::icons(),
+            icon_theme: ConfigDefault::icon_theme(),
+            styling: ConfigDefault::styling(),
+            format: ConfigDefault::format(),
+            index_sentinel: ConfigDefault::index_sentinel(),
+            select: ConfigDefault::select(),
+            select_index: ConfigDefault::select_index(),
+            smart_case: ConfigDefault::smart_case(),
+            normalize: ConfigDefault::normalize(),
         }
     }
 }
@@ -0,0 +1,238 @@
+use clap::ArgMatches;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use toml::Value;
+use yaml_rust::yaml::Yaml;
+
+/// `$XDG_CONFIG_HOME/dmenu-rs/config.toml`, with the usual fallback to `~/.config`
+fn path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("dmenu-rs").join("config.toml"))
+}
+
+/// Looks up a setting in the clap yaml, by either its name or long flag.
+/// Returns the name, long flag, and if it takes a value
+fn find_arg<'a>(yaml: &'a Yaml, key: &str) -> Option<(&'a str, &'a str, bool)> {
+    args_of(yaml).find_map(|(name, props)| {
+        let long = props["long"].as_str()?;
+        if key == name || key == long {
+            Some((name, long, props["takes_value"].as_bool().unwrap_or(false)))
+        } else {
+            None
+        }
+    })
+}
+
+/// Every argument in the clap yaml, by name
+fn args_of(yaml: &Yaml) -> impl Iterator<Item = (&str, &Yaml)> {
+    yaml["args"]
+        .as_vec()
+        .into_iter()
+        .flatten()
+        .filter_map(|arg| {
+            let (name, props) = arg.as_hash()?.iter().next()?;
+            Some((name.as_str()?, props))
+        })
+}
+
+/// The arguments `props` lists in `conflicts_with`, which is either one name or a list
+fn conflicts(props: &Yaml) -> Vec<&str> {
+    match &props["conflicts_with"] {
+        Yaml::String(name) => vec![name.as_str()],
+        Yaml::Array(names) => names.iter().filter_map(Yaml::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the command line gives `name`, or anything which conflicts with it either way
+fn overridden(yaml: &Yaml, cli: &ArgMatches, name: &str) -> bool {
+    let given = |arg: &str| cli.occurrences_of(arg) > 0;
+    given(name)
+        || args_of(yaml).any(|(arg, props)| {
+            let conflicts = conflicts(props);
+            (arg == name && conflicts.iter().any(|other| given(other)))
+                || (given(arg) && conflicts.contains(&name))
+        })
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        _ => Err(format!("`{}` should be a string or number", key)),
+    }
+}
+
+/// Settings from the config file, turned into command line arguments.
+/// Anything already given in `cli`, or which conflicts with something given there,
+/// is left out, so that flags take priority.
+///
+/// Settings are named after flags, such as `lines = 10` or `nb = "#222222"`.
+/// With `--profile NAME`, the settings in `[profile.NAME]` are used on top.
+pub fn args(yaml: &Yaml, cli: &ArgMatches) -> Result<Vec<OsString>, String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound && cli.value_of("profile").is_none() => {
+            return Ok(Vec::new())
+        }
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    settings(yaml, cli, &contents)
+        .map(|args| args.into_iter().map(OsString::from).collect())
+        .map_err(|msg| format!("{}: {}", path.display(), msg))
+}
+
+fn settings(yaml: &Yaml, cli: &ArgMatches, contents: &str) -> Result<Vec<String>, String> {
+    let mut table = match contents.parse::<Value>() {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => return Err("expected a table".to_owned()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let profiles = table.remove("profile");
+    if let Some(name) = cli.value_of("profile") {
+        let settings = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(name))
+            .and_then(Value::as_table)
+            .ok_or_else(|| format!("no [profile.{}]", name))?;
+        for (key, value) in settings {
+            table.insert(key.clone(), value.clone());
+        }
+    }
+
+    let mut args = Vec::new();
+    for (key, value) in table.iter() {
+        let (name, long, takes_value) =
+            find_arg(yaml, key).ok_or_else(|| format!("unknown setting `{}`", key))?;
+        if overridden(yaml, cli, name) {
+            continue;
+        }
+        match (value, takes_value) {
+            (Value::Boolean(true), false) => args.push(format!("--{}", long)),
+            (Value::Boolean(false), false) => {}
+            (_, false) => return Err(format!("`{}` should be true or false", key)),
+            (Value::Array(values), true) => {
+                for value in values {
+                    args.push(format!("--{}={}", long, scalar(key, value)?));
+                }
+            }
+            (value, true) => args.push(format!("--{}={}", long, scalar(key, value)?)),
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+    use yaml_rust::YamlLoader;
+
+    const YAML: &str = "
+name: dmenu
+args:
+  - lines:
+      long: lines
+      takes_value: true
+  - font:
+      long: font
+      takes_value: true
+      multiple: true
+      number_of_values: 1
+  - bottom:
+      long: bottom
+  - anchor:
+      long: anchor
+      takes_value: true
+      conflicts_with: bottom
+  - center:
+      long: center
+      conflicts_with:
+        - bottom
+        - anchor
+  - profile:
+      long: profile
+      takes_value: true
+";
+
+    fn merged(cli: &[&str], contents: &str) -> Result<Vec<String>, String> {
+        let yaml = YamlLoader::load_from_str(YAML).unwrap().pop().unwrap();
+        let cli = App::from_yaml(&yaml).get_matches_from(cli);
+        settings(&yaml, &cli, contents)
+    }
+
+    #[test]
+    fn settings_become_flags() {
+        assert_eq!(
+            merged(
+                &["dmenu"],
+                "lines = 10\nfont = [\"a\", \"b\"]\nbottom = true"
+            ),
+            Ok(vec![
+                "--bottom".to_owned(),
+                "--font=a".to_owned(),
+                "--font=b".to_owned(),
+                "--lines=10".to_owned(),
+            ])
+        );
+        assert_eq!(merged(&["dmenu"], "bottom = false"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn command_line_takes_priority() {
+        assert_eq!(
+            merged(&["dmenu", "--lines", "3"], "lines = 10"),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn conflicting_settings_are_dropped() {
+        // the command line flag lists the setting as a conflict
+        assert_eq!(
+            merged(&["dmenu", "--center"], "bottom = true"),
+            Ok(Vec::new())
+        );
+        // the setting lists the command line flag as a conflict
+        assert_eq!(
+            merged(&["dmenu", "--bottom"], "center = true\nanchor = \"top\""),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            merged(&["dmenu", "--anchor", "top"], "bottom = true\nlines = 5"),
+            Ok(vec!["--lines=5".to_owned()])
+        );
+    }
+
+    #[test]
+    fn profiles_apply_on_top() {
+        let contents = "lines = 10\n[profile.wide]\nlines = 20\nbottom = true";
+        assert_eq!(
+            merged(&["dmenu"], contents),
+            Ok(vec!["--lines=10".to_owned()])
+        );
+        assert_eq!(
+            merged(&["dmenu", "--profile", "wide"], contents),
+            Ok(vec!["--bottom".to_owned(), "--lines=20".to_owned()])
+        );
+        assert!(merged(&["dmenu", "--profile", "none"], contents).is_err());
+    }
+
+    #[test]
+    fn bad_settings_are_errors() {
+        assert!(merged(&["dmenu"], "colour = 1").is_err());
+        assert!(merged(&["dmenu"], "bottom = 1").is_err());
+        assert!(merged(&["dmenu"], "lines = [[1]]").is_err());
+    }
+}
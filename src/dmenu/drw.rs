@@ -5,30 +5,35 @@ use fontconfig::fontconfig::{
 };
 use itertools::Itertools;
 use libc::{c_int, c_uchar, c_uint, c_void, free};
-use std::{mem::MaybeUninit, ptr};
+use std::{collections::HashMap, mem::MaybeUninit, ops::Range, ptr};
 use unicode_segmentation::UnicodeSegmentation;
 use x11::xft::{
-    FcPattern, XftCharExists, XftColor, XftDraw, XftDrawCreate, XftDrawDestroy, XftDrawStringUtf8,
-    XftFontMatch, XftTextExtentsUtf8,
+    FcPattern, XftCharExists, XftColor, XftColorFree, XftDraw, XftDrawCreate, XftDrawDestroy,
+    XftDrawStringUtf8, XftFontMatch, XftTextExtentsUtf8,
 };
 use x11::xlib::{
-    AnyKey, AnyModifier, Display, Drawable, False, Window, XCloseDisplay, XCopyArea,
-    XDefaultColormap, XDefaultVisual, XDrawRectangle, XFillRectangle, XFreeGC, XFreePixmap,
-    XSetForeground, XSync, XUngrabKey, XWindowAttributes, GC,
+    AnyKey, AnyModifier, Colormap, Display, Drawable, False, Visual, Window, XCloseDisplay,
+    XCopyArea, XDefaultColormap, XDrawRectangle, XFillRectangle, XFreeColormap, XFreeGC,
+    XFreePixmap, XSetForeground, XSync, XUngrabKey, XWindowAttributes, GC,
 };
 use x11::xrender::XGlyphInfo;
 
 use crate::config::*;
 use crate::fnt::*;
 use crate::globals::*;
-use crate::item::{Direction::*, Items};
+use crate::history::History;
+use crate::icons::IconCache;
+use crate::item::{Direction::*, Item, Items, Partition};
+use crate::markup::{Run, Style};
 use crate::result::*;
+use crate::stream::StdinStream;
 
 #[derive(PartialEq, Debug)]
 pub enum TextOption<'a> {
     Prompt,
     Input,
     Other(&'a String),
+    Entry(&'a Item),
 }
 use TextOption::*;
 
@@ -40,6 +45,9 @@ pub struct Drw {
     pub root: Window,
     pub drawable: Drawable,
     pub gc: GC,
+    pub visual: *mut Visual,
+    pub depth: c_int,
+    pub cmap: Colormap,
     pub scheme: [*mut XftColor; 2],
     pub fonts: Vec<Fnt>,
     pub pseudo_globals: PseudoGlobals,
@@ -48,6 +56,10 @@ pub struct Drw {
     pub config: Config,
     pub input: String,
     pub items: Option<Items>,
+    pub stream: Option<StdinStream>,
+    pub history: Option<History>,
+    pub icons: IconCache,
+    pub style_colors: HashMap<String, Option<XftColor>>, // from --ansi and --markup
 }
 
 impl Drw {
@@ -69,11 +81,24 @@ impl Drw {
         text_opt: TextOption,
         invert: bool,
     ) -> CompResult<(c_int, Option<i32>)> {
-        let mut text: String = {
+        let (mut text, highlight, styles): (String, &[Range<usize>], &[Run]) = {
             match text_opt {
-                Prompt => self.config.prompt.clone(),
-                Input => self.format_input()?,
-                Other(string) => string.to_string(),
+                Prompt => match &self.pseudo_globals.match_error {
+                    Some(error) => (error.clone(), &[], &[]),
+                    None => (self.prompt_text(), &[], &[]),
+                },
+                Input => (self.format_input()?, &[], &[]),
+                Other(string) => (string.to_string(), &[], &[]),
+                Entry(item) => (
+                    item.display.clone(),
+                    // highlights are found in what's searched, so only line up if it's the same
+                    if item.search == item.display {
+                        &item.highlight
+                    } else {
+                        &[]
+                    },
+                    &item.styles,
+                ),
             }
         };
         unsafe {
@@ -94,24 +119,26 @@ impl Drw {
                     (*self.scheme[if invert { ColFg } else { ColBg } as usize]).pixel,
                 );
                 XFillRectangle(self.dpy, self.drawable, self.gc, x, y, w as u32, h);
-                d = XftDrawCreate(
-                    self.dpy,
-                    self.drawable,
-                    XDefaultVisual(self.dpy, self.screen),
-                    XDefaultColormap(self.dpy, self.screen),
-                );
+                d = XftDrawCreate(self.dpy, self.drawable, self.visual, self.cmap);
                 x += lpad as c_int;
                 w -= lpad;
+                for (_, style) in styles {
+                    for color in style.fg.iter().chain(style.bg.iter()) {
+                        self.style_color(color);
+                    }
+                }
             }
 
             let mut slice_start = 0;
             let mut slice_end = 0;
             let mut cur_font: Option<usize> = None;
+            let mut cur_highlight = false;
+            let mut cur_style: Option<usize> = None;
             let mut spool = Spool::new();
 
             text.push_str("."); // this will be removed later; turned into elipses
 
-            for cur_char in text.chars() {
+            for (i, cur_char) in text.char_indices() {
                 // String is already utf8 so we don't need to do extra conversions
                 // As such, this logic is changed from the source dmenu quite a bit
 
@@ -119,11 +146,14 @@ impl Drw {
                     .fonts
                     .iter()
                     .position(|font| XftCharExists(self.dpy, font.xfont, cur_char as u32) == 1);
-                if cur_font.is_some() && cur_font == found_font {
+                let highlighted = highlight.iter().any(|range| range.contains(&i));
+                let style = styles.iter().position(|(range, _)| range.contains(&i));
+                let same_run = cur_highlight == highlighted && cur_style == style;
+                if cur_font.is_some() && cur_font == found_font && same_run {
                     // append to list to be printed
                     slice_end += cur_char.len_utf8();
                 }
-                if cur_font.is_none() || cur_font != found_font {
+                if cur_font.is_none() || cur_font != found_font || !same_run {
                     if found_font.is_none() {
                         // char is not found in any fonts
                         // In this case, pretend it's in the first font, as it must be drawn
@@ -171,7 +201,7 @@ impl Drw {
                             }
                         }
                     }
-                    // Need to switch fonts
+                    // Need to switch fonts (or highlighting, or style)
                     // First, take care of the stuff pending print
                     if cur_font.is_some() {
                         spool.push((
@@ -179,10 +209,14 @@ impl Drw {
                                 text.as_bytes()[slice_start..slice_end].to_vec(),
                             ),
                             cur_font,
+                            cur_highlight,
+                            cur_style,
                         ));
                     }
                     // Then, set up next thing to print
                     cur_font = found_font;
+                    cur_highlight = highlighted;
+                    cur_style = style;
                     slice_start = slice_end;
                     slice_end += cur_char.len_utf8();
                 }
@@ -191,6 +225,8 @@ impl Drw {
             spool.push((
                 String::from_utf8_unchecked(text.as_bytes()[slice_start..slice_end].to_vec()),
                 cur_font,
+                cur_highlight,
+                cur_style,
             ));
 
             let padded_width = w - self.pseudo_globals.lrpad as u32 / 2;
@@ -200,9 +236,21 @@ impl Drw {
             }
 
             let elip_width = spool.elip_width(&self);
-            for (slice, font) in spool.into_iter() {
+            for (slice, font, highlighted, style) in spool.into_iter() {
                 // Do early truncation (...)
-                self.render(&mut x, &y, &mut w, &h, slice, &font, d, render, invert);
+                self.render(
+                    &mut x,
+                    &y,
+                    &mut w,
+                    &h,
+                    slice,
+                    &font,
+                    d,
+                    render,
+                    invert,
+                    highlighted,
+                    style.map(|i| &styles[i].1),
+                );
             }
 
             if d != ptr::null_mut() {
@@ -224,6 +272,8 @@ impl Drw {
         d: *mut XftDraw,
         render: bool,
         invert: bool,
+        highlighted: bool,
+        style: Option<&Style>,
     ) {
         if text.len() == 0 {
             return;
@@ -234,22 +284,72 @@ impl Drw {
             let (substr_width, _) =
                 self.font_getexts(font_ref, text.as_ptr() as *mut c_uchar, text.len() as c_int);
             if render {
-                let ty = *y + (*h as i32 - usedfont.height as i32) / 2 + (*usedfont.xfont).ascent;
-                XftDrawStringUtf8(
-                    d,
-                    self.scheme[if invert { ColBg } else { ColFg } as usize],
-                    self.fonts[cur_font.unwrap()].xfont,
-                    *x,
-                    ty,
-                    text.as_ptr() as *mut c_uchar,
-                    text.len() as c_int,
-                );
+                // highlighting takes priority over styling
+                let style = style.filter(|_| !highlighted && !invert);
+                let style_color = |color: &Option<String>| {
+                    color
+                        .as_ref()
+                        .and_then(|color| self.style_colors.get(color))
+                        .and_then(|color| color.as_ref())
+                        .map(|color| color as *const XftColor as *mut XftColor)
+                };
+                let color = if highlighted {
+                    let highlight = self.pseudo_globals.schemeset[SchemeHighlight as usize];
+                    // otherwise, drawn over the item's own background
+                    if !self.config.colors[SchemeHighlight as usize][ColBg as usize].is_empty() {
+                        XSetForeground(self.dpy, self.gc, (*highlight[ColBg as usize]).pixel);
+                        XFillRectangle(self.dpy, self.drawable, self.gc, *x, *y, substr_width, *h);
+                    }
+                    highlight[ColFg as usize]
+                } else {
+                    if let Some(bg) = style.and_then(|style| style_color(&style.bg)) {
+                        XSetForeground(self.dpy, self.gc, (*bg).pixel);
+                        XFillRectangle(self.dpy, self.drawable, self.gc, *x, *y, substr_width, *h);
+                    }
+                    style
+                        .and_then(|style| style_color(&style.fg))
+                        .unwrap_or(self.scheme[if invert { ColBg } else { ColFg } as usize])
+                };
+                let ty = self.baseline(*y, *h);
+                let bold = style.is_some_and(|style| style.bold);
+                // without a bold face at hand, bold is drawn twice a pixel apart
+                for dx in 0..if bold { 2 } else { 1 } {
+                    XftDrawStringUtf8(
+                        d,
+                        color,
+                        self.fonts[cur_font.unwrap()].xfont,
+                        *x + dx,
+                        ty,
+                        text.as_ptr() as *mut c_uchar,
+                        text.len() as c_int,
+                    );
+                }
+                if style.is_some_and(|style| style.underline) {
+                    XSetForeground(self.dpy, self.gc, (*color).pixel);
+                    XFillRectangle(
+                        self.dpy,
+                        self.drawable,
+                        self.gc,
+                        *x,
+                        ty + 1,
+                        substr_width,
+                        1,
+                    );
+                }
             }
             *x += substr_width as i32;
             *w -= substr_width;
         }
     }
 
+    /// Where text sits in a line `h` tall. The first font's metrics are used
+    /// for every font, so that fallback glyphs share its baseline, centered vertically
+    /// whatever the padding.
+    fn baseline(&self, y: c_int, h: c_uint) -> c_int {
+        let font = &self.fonts[0];
+        unsafe { y + (h as c_int - font.height as c_int) / 2 + (*font.xfont).ascent }
+    }
+
     pub fn font_getexts(
         &self,
         font: &Fnt,
@@ -264,22 +364,36 @@ impl Drw {
         }
     }
 
+    /// The prompt, after the match mode if it's been changed from substring,
+    /// so that cycling through modes shows where it's at
+    fn prompt_text(&self) -> String {
+        match self.config.match_mode {
+            MatchMode::Substring => self.config.prompt.clone(),
+            mode if self.config.prompt.is_empty() => format!("[{}]", mode.name()),
+            mode => format!("[{}] {}", mode.name(), self.config.prompt),
+        }
+    }
+
     pub fn draw(&mut self) -> CompResult<()> {
         // drawmenu
-        self.pseudo_globals.promptw = if self.config.prompt.len() != 0 {
-            self.textw(Prompt)?
-        } else {
-            0
-        };
+        // matching goes first, as an invalid pattern is reported in the prompt area
+        self.pseudo_globals.match_error = None;
+        let pre_processed_items = self.gen_matches()?;
+        let items_to_draw = self.postprocess_matches(pre_processed_items)?;
+        self.items.as_mut().unwrap().apply_preselect(&items_to_draw);
+
+        let show_prompt =
+            !self.prompt_text().is_empty() || self.pseudo_globals.match_error.is_some();
+        self.pseudo_globals.promptw = if show_prompt { self.textw(Prompt)? } else { 0 };
 
         self.setscheme(SchemeNorm);
         self.rect(0, 0, self.w as u32, self.h as u32, true, true); // clear menu
 
         let mut x = 0;
 
-        if self.config.prompt.len() > 0 {
+        if show_prompt {
             // draw prompt
-            self.setscheme(SchemeSel);
+            self.setscheme(SchemePrompt);
             x = self
                 .text(
                     x,
@@ -293,22 +407,62 @@ impl Drw {
                 .0;
         }
 
+        let counter = format!("{}/{}", items_to_draw.len(), self.get_items().len());
+        let counterw = if self.config.counter {
+            self.textw(Other(&counter))?
+        } else {
+            0
+        };
+        let scrollbarw = if self.config.scrollbar && self.config.lines > 0 {
+            (self.pseudo_globals.lrpad / 2).max(2)
+        } else {
+            0
+        };
+
+        // items are laid out in what the counter or scrollbar leave
+        let full_w = self.w;
+        self.w -= if self.config.lines == 0 {
+            counterw
+        } else {
+            scrollbarw
+        };
         let matches = Items::draw(
             self,
-            if self.config.lines > 0 {
-                Vertical
-            } else {
+            items_to_draw,
+            if self.config.lines == 0 {
                 Horizontal
+            } else if self.config.columns > 1 {
+                Grid
+            } else {
+                Vertical
             },
-        )?;
+        );
+        self.w = full_w;
+        let matches = matches?;
+
+        if self.config.counter {
+            self.setscheme(SchemeCounter);
+            self.text(
+                self.w - counterw,
+                0,
+                counterw as c_uint,
+                self.pseudo_globals.bh as c_uint,
+                self.pseudo_globals.lrpad as c_uint / 2,
+                Other(&counter),
+                false,
+            )?;
+        }
+        if scrollbarw > 0 {
+            self.draw_scrollbar(scrollbarw);
+        }
 
         /* draw input field */
         let w =
             if self.config.lines > 0 || self.items.as_mut().unwrap().match_len() == 0 || !matches {
-                self.w - x
+                self.w - counterw - x
             } else {
                 if self.config.render_overrun {
-                    self.textw(Input)?.min(self.w - x)
+                    self.textw(Input)?.min(self.w - counterw - x)
                 } else {
                     self.pseudo_globals.inputw
                 }
@@ -339,12 +493,13 @@ impl Drw {
 
         if curpos < truncated.unwrap_or(w - self.pseudo_globals.lrpad / 2) {
             self.setscheme(SchemeNorm);
-            let tallest_font = self.fonts.iter().map(|f| f.height).max().unwrap();
+            // as tall as the text, but no taller than the bar
+            let cursorh = self.fonts[0].height.min(self.pseudo_globals.bh).max(5) - 4;
             self.rect(
                 x + curpos,
-                (self.pseudo_globals.bh - tallest_font) as i32 / 2 + 2,
+                (self.pseudo_globals.bh - cursorh) as i32 / 2,
                 2,
-                tallest_font - 4,
+                cursorh,
                 true,
                 false,
             );
@@ -354,6 +509,31 @@ impl Drw {
         Ok(())
     }
 
+    /// The track runs down the right of the list, with the thumb at the
+    /// current page's place among the pages of matches
+    fn draw_scrollbar(&mut self, w: c_int) {
+        let bh = self.pseudo_globals.bh as c_int;
+        let spacing = self.config.line_spacing as c_int;
+        let (x, y, h) = (self.w - w, bh + spacing, self.h - bh - spacing);
+        self.setscheme(SchemeScrollbar);
+        self.rect(x, y, w as c_uint, h as c_uint, true, true);
+        let pages = self.items.as_ref().unwrap().match_len() as c_int;
+        if pages == 0 {
+            return; // nothing matched
+        }
+        let (_, page) = Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
+        let top = y + h * page as c_int / pages;
+        let bottom = y + h * (page as c_int + 1) / pages;
+        self.rect(
+            x,
+            top,
+            w as c_uint,
+            (bottom - top).max(1) as c_uint,
+            true,
+            false,
+        );
+    }
+
     pub fn map(&self, win: Window, x: c_int, y: c_int, w: c_int, h: c_int) {
         unsafe {
             XCopyArea(
@@ -377,11 +557,24 @@ impl Drw {
             .map(|computed_width| computed_width + self.pseudo_globals.lrpad)
     }
 
+    /// Allocates a color used by `--ansi` or `--markup`, once.
+    /// Colors which can't be parsed are drawn with the scheme's instead.
+    fn style_color(&mut self, name: &str) {
+        if !self.style_colors.contains_key(name) {
+            let mut color = MaybeUninit::<XftColor>::uninit();
+            let color = match self.clr_create(color.as_mut_ptr(), name) {
+                Ok(()) => Some(unsafe { color.assume_init() }),
+                Err(_) => None,
+            };
+            self.style_colors.insert(name.to_owned(), color);
+        }
+    }
+
     pub fn setscheme(&mut self, scm: Schemes) {
         self.scheme = self.pseudo_globals.schemeset[scm as usize];
     }
 
-    fn rect(&self, x: c_int, y: c_int, w: c_uint, h: c_uint, filled: bool, invert: bool) {
+    pub fn rect(&self, x: c_int, y: c_int, w: c_uint, h: c_uint, filled: bool, invert: bool) {
         unsafe {
             XSetForeground(
                 self.dpy,
@@ -404,21 +597,31 @@ impl Drop for Drw {
                 font.free(self.dpy);
             }
             XUngrabKey(self.dpy, AnyKey, AnyModifier, self.root);
+            self.icons_free();
             for i in 0..SchemeLast as usize {
                 free(self.pseudo_globals.schemeset[i][0] as *mut c_void);
                 free(self.pseudo_globals.schemeset[i][1] as *mut c_void);
             }
+            for color in self.style_colors.values_mut().flatten() {
+                XftColorFree(self.dpy, self.visual, self.cmap, color);
+            }
             XFreePixmap(self.dpy, self.drawable);
             XFreeGC(self.dpy, self.gc);
+            if self.cmap != XDefaultColormap(self.dpy, self.screen) {
+                XFreeColormap(self.dpy, self.cmap); // made for --argb
+            }
             XSync(self.dpy, False);
             XCloseDisplay(self.dpy);
         }
     }
 }
 
-// Utility struct; contains chars and fonts
+// Chars, their font, whether they're highlighted, and their style
+type Slice = (String, Option<usize>, bool, Option<usize>);
+
+// Utility struct; contains slices to be drawn
 struct Spool {
-    data: Vec<(String, Option<usize>)>,
+    data: Vec<Slice>,
     elipsed: bool,
 }
 
@@ -432,7 +635,7 @@ impl Spool {
     pub fn width(&self, drw: &Drw) -> u32 {
         self.data
             .iter()
-            .map(|(slice, font)| {
+            .map(|(slice, font, ..)| {
                 drw.font_getexts(
                     &drw.fonts[font.unwrap()],
                     slice.as_ptr() as *mut c_uchar,
@@ -451,7 +654,7 @@ impl Spool {
             self.push(elipse);
         }
     }
-    fn pop(&mut self) -> (String, Option<usize>) {
+    fn pop(&mut self) -> Slice {
         let len = self.data.len();
         if self.data[len - 1].0.len() == 1 {
             self.data.pop().unwrap()
@@ -459,6 +662,8 @@ impl Spool {
             (
                 self.data[len - 1].0.pop().unwrap().to_string(),
                 self.data[len - 1].1,
+                self.data[len - 1].2,
+                self.data[len - 1].3,
             )
         }
     }
@@ -476,10 +681,10 @@ impl Spool {
             }
         }
     }
-    pub fn push(&mut self, arg: (String, Option<usize>)) {
+    pub fn push(&mut self, arg: Slice) {
         self.data.push(arg);
     }
-    pub fn into_iter(self) -> std::vec::IntoIter<(String, Option<usize>)> {
+    pub fn into_iter(self) -> std::vec::IntoIter<Slice> {
         self.data.into_iter()
     }
     pub fn elip_width(&self, drw: &Drw) -> Option<i32> {
@@ -493,7 +698,7 @@ impl Spool {
                     .iter()
                     .rev()
                     .skip(3)
-                    .map(|(slice, font)| {
+                    .map(|(slice, font, ..)| {
                         drw.font_getexts(
                             &drw.fonts[font.unwrap()],
                             slice.as_ptr() as *mut c_uchar,
@@ -14,6 +14,7 @@ pub struct PseudoGlobals {
     pub win: Window,
     pub cursor: usize,
     pub xic: XIC,
+    pub match_error: Option<String>, // shown instead of the prompt
 }
 
 impl Default for PseudoGlobals {
@@ -27,6 +28,7 @@ impl Default for PseudoGlobals {
             win: 0,
             cursor: 0,
             xic: ptr::null_mut(),
+            match_error: None,
         }
     }
 }
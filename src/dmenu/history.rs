@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::drw::Drw;
+use crate::result::*;
+
+#[derive(Debug)]
+struct Entry {
+    count: u64,
+    last_used: u64, // seconds since the epoch
+}
+
+/// Previously selected items, kept between runs for `--history`
+///
+/// Stored one per line as `count<TAB>last_used<TAB>text`
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl History {
+    /// A missing file is just an empty history
+    pub fn load(path: &str) -> CompResult<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Die::stderr(format!("--history: could not read {}: {}", path, e)),
+        };
+        let mut entries = HashMap::new();
+        for line in contents.lines() {
+            let mut fields = line.splitn(3, '\t');
+            if let (Some(count), Some(last_used), Some(text)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                if let (Ok(count), Ok(last_used)) = (count.parse(), last_used.parse()) {
+                    entries.insert(text.to_owned(), Entry { count, last_used });
+                }
+            }
+        }
+        Ok(Self {
+            path: PathBuf::from(path),
+            entries,
+        })
+    }
+
+    /// How often an item has been picked, weighted towards recent picks
+    pub fn frecency(&self, text: &str) -> u64 {
+        match self.entries.get(text) {
+            Some(entry) => {
+                let age = now().saturating_sub(entry.last_used);
+                entry.count
+                    * match age {
+                        0..=3599 => 8,       // past hour
+                        3600..=86399 => 4,   // past day
+                        86400..=604799 => 2, // past week
+                        _ => 1,
+                    }
+            }
+            None => 0,
+        }
+    }
+
+    fn record(&mut self, text: &str) {
+        let entry = self.entries.entry(text.to_owned()).or_insert(Entry {
+            count: 0,
+            last_used: 0,
+        });
+        entry.count += 1;
+        entry.last_used = now();
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for (text, entry) in &self.entries {
+            contents.push_str(&format!("{}\t{}\t{}\n", entry.count, entry.last_used, text));
+        }
+        // write then rename, so an interrupted write can't lose the whole history
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+impl Drw {
+    /// Score an item's text by `--history`, 0 if not in use
+    pub fn frecency(&self, text: &str) -> u64 {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.frecency(text))
+    }
+
+    /// Count a selection towards `--history`, in memory until `save_history`
+    pub fn record_history(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.record(text);
+        }
+    }
+
+    /// Write `--history` out, once the selections are recorded. Failing to save
+    /// is only a warning, the selections are still output.
+    pub fn save_history(&self) {
+        if let Some(history) = self.history.as_ref() {
+            if let Err(e) = history.save() {
+                eprintln!(
+                    "--history: could not write {}: {}",
+                    history.path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[(&str, u64, u64)]) -> History {
+        History {
+            path: PathBuf::new(),
+            entries: entries
+                .iter()
+                .map(|(text, count, last_used)| {
+                    let entry = Entry {
+                        count: *count,
+                        last_used: *last_used,
+                    };
+                    (text.to_string(), entry)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn frecency_weights_recent_picks() {
+        let now = now();
+        let history = history(&[
+            ("hour", 3, now - 60),
+            ("day", 3, now - 7200),
+            ("week", 3, now - 2 * 86400),
+            ("old", 3, now - 30 * 86400),
+        ]);
+        assert_eq!(history.frecency("hour"), 24);
+        assert_eq!(history.frecency("day"), 12);
+        assert_eq!(history.frecency("week"), 6);
+        assert_eq!(history.frecency("old"), 3);
+        assert_eq!(history.frecency("never"), 0);
+    }
+
+    #[test]
+    fn frequent_beats_recent() {
+        let now = now();
+        let history = history(&[("often", 20, now - 30 * 86400), ("once", 1, now)]);
+        assert!(history.frecency("often") > history.frecency("once"));
+    }
+
+    #[test]
+    fn record_counts_and_refreshes() {
+        let mut history = history(&[("old", 2, 0)]);
+        history.record("old");
+        history.record("new");
+        assert_eq!(history.frecency("old"), 3 * 8);
+        assert_eq!(history.frecency("new"), 8);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = std::env::temp_dir().join(format!("dmenu-history-{}", std::process::id()));
+        let mut saved = History::load(path.to_str().unwrap()).ok().unwrap();
+        assert!(saved.entries.is_empty());
+        saved.record("with\ttab");
+        saved.record("plain");
+        saved.record("plain");
+        saved.save().unwrap();
+
+        let loaded = History::load(path.to_str().unwrap()).ok().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.frecency("plain"), 16);
+        assert_eq!(loaded.frecency("with\ttab"), 8);
+    }
+}
@@ -0,0 +1,520 @@
+use libc::{c_char, c_int, c_uint, c_void};
+use png::{ColorType, Decoder, Transformations};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File};
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use x11::xlib::{
+    XCreateGC, XCreateImage, XCreatePixmap, XFree, XFreeGC, XFreePixmap, XParseColor, XPutImage,
+    ZPixmap,
+};
+use x11::xrender::{
+    PictOpOver, PictStandardARGB32, Picture, XRenderComposite, XRenderCreatePicture,
+    XRenderFindStandardFormat, XRenderFindVisualFormat, XRenderFreePicture,
+};
+
+use crate::drw::Drw;
+
+/// A loaded icon, ready to be composited
+#[derive(Debug, Clone, Copy)]
+struct Icon {
+    picture: Picture,
+    w: c_uint,
+    h: c_uint,
+}
+
+/// Icons which have been drawn, by name. None if an icon couldn't be loaded,
+/// so that it isn't searched for again on every draw.
+#[derive(Debug, Default)]
+pub struct IconCache {
+    icons: HashMap<String, Option<Icon>>,
+    target: Picture,                 // the drawable, created with the first icon
+    themes: Option<Vec<ThemeIndex>>, // indexed icon themes, in lookup order
+}
+
+/// The icons of a theme by name, with the size of each
+type ThemeIndex = HashMap<String, (u32, PathBuf)>;
+
+/// Straight (not premultiplied) RGBA pixels
+struct Image {
+    w: usize,
+    h: usize,
+    rgba: Vec<[u8; 4]>,
+}
+
+/// Splits a line in rofi's `TEXT\0icon\x1fICON` syntax into its text and icon.
+/// Any other `\x1f` seperated properties after the NUL are ignored.
+pub fn split_line(line: String) -> (String, Option<String>) {
+    let nul = match line.find('\0') {
+        Some(nul) => nul,
+        None => return (line, None),
+    };
+    let mut properties = line[nul + 1..].split('\x1f');
+    let mut icon = None;
+    while let (Some(key), Some(value)) = (properties.next(), properties.next()) {
+        if key == "icon" && !value.is_empty() {
+            icon = Some(value.to_owned());
+        }
+    }
+    (line[..nul].to_owned(), icon)
+}
+
+/// Where icon themes are installed, most important first
+fn theme_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let mut dirs = Vec::new();
+    match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => dirs.push(PathBuf::from(dir).join("icons")),
+        None => dirs.extend(home.as_ref().map(|home| home.join(".local/share/icons"))),
+    }
+    dirs.extend(home.map(|home| home.join(".icons")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    dirs.extend(data_dirs.split(':').map(|dir| Path::new(dir).join("icons")));
+    dirs
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+/// The icon size held by a theme directory named like `48x48`, `48x48@2` or `48`
+fn dir_size(name: Option<&OsStr>) -> Option<u32> {
+    name?
+        .to_str()?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Whether an icon of size `new` suits `size` better than one of size `old`:
+/// the smallest size which is at least `size`, so it can be scaled down
+fn better(size: u32, new: u32, old: u32) -> bool {
+    match (new >= size, old >= size) {
+        (true, true) => new < old,
+        (false, false) => new > old,
+        (new_fits, _) => new_fits,
+    }
+}
+
+/// The themes listed by `Inherits=` in the `[Icon Theme]` section of an `index.theme`
+fn inherits(index_theme: &str) -> Vec<String> {
+    let mut section = "";
+    for line in index_theme.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line;
+        } else if let Some(themes) = line.strip_prefix("Inherits=") {
+            if section == "[Icon Theme]" {
+                return themes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|theme| !theme.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// `theme` and everything it inherits from, ending with hicolor, in lookup order
+fn theme_chain(theme: &str, dirs: &[PathBuf]) -> Vec<String> {
+    let mut chain = vec![theme.to_owned()];
+    let mut i = 0;
+    while i < chain.len() {
+        let parents = dirs
+            .iter()
+            .find_map(|dir| fs::read_to_string(dir.join(&chain[i]).join("index.theme")).ok())
+            .map_or_else(Vec::new, |contents| inherits(&contents));
+        for parent in parents {
+            if !chain.contains(&parent) {
+                chain.push(parent);
+            }
+        }
+        i += 1;
+    }
+    if !chain.iter().any(|theme| theme == "hicolor") {
+        chain.push("hicolor".to_owned());
+    }
+    chain
+}
+
+/// Every PNG and XPM icon in `theme` by name, with the size which suits `size` best
+fn index_theme(theme: &str, dirs: &[PathBuf], size: u32) -> ThemeIndex {
+    let mut index: ThemeIndex = HashMap::new();
+    for dir in dirs {
+        // themes are laid out as either SIZE/CONTEXT or CONTEXT/SIZE
+        for outer in subdirs(&dir.join(theme)) {
+            for inner in subdirs(&outer) {
+                let icon_size = match dir_size(inner.file_name()).or(dir_size(outer.file_name())) {
+                    Some(icon_size) => icon_size,
+                    None => continue, // such as scalable, which only has svgs
+                };
+                for path in fs::read_dir(&inner)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                {
+                    let png = match path.extension().and_then(OsStr::to_str) {
+                        Some("png") => true,
+                        Some("xpm") => false,
+                        _ => continue,
+                    };
+                    let name = match path.file_stem().and_then(OsStr::to_str) {
+                        Some(name) => name.to_owned(),
+                        None => continue,
+                    };
+                    let replace = match index.get(&name) {
+                        Some((old, old_path)) => {
+                            better(size, icon_size, *old)
+                                || (icon_size == *old
+                                    && png
+                                    && old_path.extension() != Some(OsStr::new("png")))
+                        }
+                        None => true,
+                    };
+                    if replace {
+                        index.insert(name, (icon_size, path));
+                    }
+                }
+            }
+        }
+    }
+    index
+}
+
+impl IconCache {
+    /// Finds an icon by name in the theme, the themes it inherits, hicolor and then
+    /// `/usr/share/pixmaps`. The themes are indexed on the first lookup, so they're
+    /// only listed once.
+    fn find(&mut self, name: &str, theme: &str, size: u32) -> Option<PathBuf> {
+        let themes = self.themes.get_or_insert_with(|| {
+            let dirs = theme_dirs();
+            theme_chain(theme, &dirs)
+                .iter()
+                .map(|theme| index_theme(theme, &dirs, size))
+                .collect()
+        });
+        if let Some((_, path)) = themes.iter().find_map(|index| index.get(name)) {
+            return Some(path.clone());
+        }
+        [format!("{}.png", name), format!("{}.xpm", name)]
+            .iter()
+            .map(|file| Path::new("/usr/share/pixmaps").join(file))
+            .find(|path| path.is_file())
+    }
+}
+
+fn decode_png(path: &Path) -> Option<Image> {
+    let mut decoder = Decoder::new(File::open(path).ok()?);
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let mut reader = decoder.read_info().ok()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).ok()?;
+    let buf = &buf[..info.buffer_size()];
+    let rgba = match info.color_type {
+        ColorType::Rgba => buf
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect(),
+        ColorType::Rgb => buf
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ColorType::Grayscale => buf.iter().map(|g| [*g, *g, *g, 255]).collect(),
+        ColorType::Indexed => return None, // expanded to RGB(A) by the decoder
+    };
+    Some(Image {
+        w: info.width as usize,
+        h: info.height as usize,
+        rgba,
+    })
+}
+
+/// The color key `c` of an XPM color line, such as `c #ff0000` or `c None`
+fn xpm_color(spec: &str, named: &impl Fn(&str) -> Option<[u8; 3]>) -> Option<[u8; 4]> {
+    let words: Vec<&str> = spec.split_whitespace().collect();
+    let start = words.iter().position(|word| *word == "c")? + 1;
+    // a value can be several words, such as `light gray`, up to the next key
+    let end = words[start..]
+        .iter()
+        .position(|word| ["m", "s", "g", "g4"].contains(word))
+        .map_or(words.len(), |i| start + i);
+    let value = words[start..end].join(" ");
+    if value.eq_ignore_ascii_case("none") {
+        return Some([0; 4]);
+    }
+    let [r, g, b] = named(&value)?;
+    Some([r, g, b, 255])
+}
+
+/// XPM3 images. Colors are looked up with `named`, which also handles `#rrggbb`.
+fn decode_xpm(text: &str, named: impl Fn(&str) -> Option<[u8; 3]>) -> Option<Image> {
+    // everything needed is in the C string literals
+    let mut strings = text.split('"').skip(1).step_by(2);
+    let header: Vec<usize> = strings
+        .next()?
+        .split_whitespace()
+        .take(4) // there may be a hotspot and XPMEXT after
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    let (w, h, ncolors, cpp) = match header[..] {
+        [w, h, ncolors, cpp] if cpp > 0 => (w, h, ncolors, cpp),
+        _ => return None,
+    };
+    let mut colors = HashMap::new();
+    for _ in 0..ncolors {
+        let line = strings.next()?;
+        colors.insert(line.get(..cpp)?, xpm_color(line.get(cpp..)?, &named)?);
+    }
+    let mut rgba = Vec::with_capacity(w * h);
+    for _ in 0..h {
+        let row = strings.next()?;
+        for x in 0..w {
+            rgba.push(*colors.get(row.get(x * cpp..(x + 1) * cpp)?)?);
+        }
+    }
+    Some(Image { w, h, rgba })
+}
+
+/// The pixels of `old` which pixel `i` of `new` covers, when scaling between them
+fn span(i: usize, new: usize, old: usize) -> (usize, usize) {
+    let start = i * old / new;
+    (start, ((i + 1) * old / new).max(start + 1))
+}
+
+impl Image {
+    /// Scaled to fit in a `size` square, averaging the pixels each new one covers.
+    /// The pixels are premultiplied ARGB, as XRender wants them.
+    fn scale(&self, size: usize) -> (usize, usize, Vec<u32>) {
+        let longest = self.w.max(self.h);
+        let w = (self.w * size / longest).max(1);
+        let h = (self.h * size / longest).max(1);
+        let mut pixels = Vec::with_capacity(w * h);
+        for y in 0..h {
+            let (y0, y1) = span(y, h, self.h);
+            for x in 0..w {
+                let (x0, x1) = span(x, w, self.w);
+                let mut sum = [0u32; 4];
+                for row in y0..y1 {
+                    for &[r, g, b, a] in &self.rgba[row * self.w + x0..row * self.w + x1] {
+                        let a = a as u32;
+                        sum[0] += a;
+                        sum[1] += r as u32 * a / 255;
+                        sum[2] += g as u32 * a / 255;
+                        sum[3] += b as u32 * a / 255;
+                    }
+                }
+                let n = ((y1 - y0) * (x1 - x0)) as u32;
+                let [a, r, g, b] = sum.map(|channel| channel / n);
+                pixels.push(a << 24 | r << 16 | g << 8 | b);
+            }
+        }
+        (w, h, pixels)
+    }
+}
+
+impl Drw {
+    fn icon_image(&self, path: &Path) -> Option<Image> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("xpm") => decode_xpm(&fs::read_to_string(path).ok()?, |name| unsafe {
+                let name = CString::new(name).ok()?;
+                let mut color = MaybeUninit::uninit();
+                if XParseColor(self.dpy, self.cmap, name.as_ptr(), color.as_mut_ptr()) == 0 {
+                    return None;
+                }
+                let color = color.assume_init();
+                Some([
+                    (color.red >> 8) as u8,
+                    (color.green >> 8) as u8,
+                    (color.blue >> 8) as u8,
+                ])
+            }),
+            _ => decode_png(path),
+        }
+    }
+
+    /// Loads an icon from a path or the icon theme, scaled to the height of an item
+    fn icon_load(&mut self, name: &str) -> Option<Icon> {
+        let size = self.pseudo_globals.bh;
+        let path = if name.contains('/') {
+            PathBuf::from(name)
+        } else {
+            self.icons.find(name, &self.config.icon_theme, size)?
+        };
+        let image = self.icon_image(&path)?;
+        if image.w == 0 || image.h == 0 {
+            return None;
+        }
+        let (w, h, mut pixels) = image.scale(size as usize);
+        let (w, h) = (w as c_uint, h as c_uint);
+        unsafe {
+            let pixmap = XCreatePixmap(self.dpy, self.root, w, h, 32);
+            let gc = XCreateGC(self.dpy, pixmap, 0, ptr::null_mut());
+            let ximage = XCreateImage(
+                self.dpy,
+                ptr::null_mut(),
+                32,
+                ZPixmap,
+                0,
+                pixels.as_mut_ptr() as *mut c_char,
+                w,
+                h,
+                32,
+                0,
+            );
+            if !ximage.is_null() {
+                XPutImage(self.dpy, pixmap, gc, ximage, 0, 0, 0, 0, w, h);
+                XFree(ximage as *mut c_void); // not XDestroyImage, the pixels are still ours
+            }
+            XFreeGC(self.dpy, gc);
+            let picture = XRenderCreatePicture(
+                self.dpy,
+                pixmap,
+                XRenderFindStandardFormat(self.dpy, PictStandardARGB32),
+                0,
+                ptr::null(),
+            );
+            XFreePixmap(self.dpy, pixmap); // the picture keeps it around
+            if ximage.is_null() {
+                XRenderFreePicture(self.dpy, picture);
+                return None;
+            }
+            Some(Icon { picture, w, h })
+        }
+    }
+
+    /// Fills a `w` wide space for an icon, then draws `name` centered in it.
+    /// Icons which can't be found or decoded leave the space blank.
+    pub fn icon(&mut self, name: Option<&str>, x: c_int, y: c_int, w: c_int) {
+        let bh = self.pseudo_globals.bh;
+        if w <= 0 {
+            return;
+        }
+        self.rect(x, y, w as c_uint, bh, true, true);
+        let name = match name {
+            Some(name) => name,
+            None => return,
+        };
+        let icon = match self.icons.icons.get(name) {
+            Some(icon) => *icon,
+            None => {
+                let icon = self.icon_load(name);
+                self.icons.icons.insert(name.to_owned(), icon);
+                icon
+            }
+        };
+        let icon = match icon {
+            Some(icon) => icon,
+            None => return,
+        };
+        unsafe {
+            if self.icons.target == 0 {
+                self.icons.target = XRenderCreatePicture(
+                    self.dpy,
+                    self.drawable,
+                    XRenderFindVisualFormat(self.dpy, self.visual),
+                    0,
+                    ptr::null(),
+                );
+            }
+            XRenderComposite(
+                self.dpy,
+                PictOpOver,
+                icon.picture,
+                0,
+                self.icons.target,
+                0,
+                0,
+                0,
+                0,
+                x + (w - icon.w as c_int).max(0) / 2,
+                y + (bh - icon.h) as c_int / 2,
+                icon.w.min(w as c_uint),
+                icon.h,
+            );
+        }
+    }
+
+    pub fn icons_free(&mut self) {
+        unsafe {
+            for icon in self.icons.icons.values().flatten() {
+                XRenderFreePicture(self.dpy, icon.picture);
+            }
+            if self.icons.target != 0 {
+                XRenderFreePicture(self.dpy, self.icons.target);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inherited_themes() {
+        let index = "[Icon Theme]\nName=Papirus\nInherits=breeze, hicolor\n\n\
+                     [48x48/apps]\nInherits=ignored\n";
+        assert_eq!(inherits(index), ["breeze", "hicolor"]);
+        assert!(inherits("[Other]\nInherits=a").is_empty());
+    }
+
+    #[test]
+    fn indexes_theme_chain() {
+        let dir = env::temp_dir().join(format!("dmenu-icons-{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write("child/index.theme", "[Icon Theme]\nInherits=parent\n");
+        write(
+            "parent/index.theme",
+            "[Icon Theme]\nInherits=child,hicolor\n",
+        );
+        write("child/16x16/apps/term.png", "");
+        write("child/64x64/apps/term.png", "");
+        write("child/apps/32/term.xpm", "");
+        write("child/apps/32/term.png", "");
+        write("child/scalable/apps/term.svg", "");
+        write("parent/48x48/apps/editor.png", "");
+
+        let dirs = [dir.clone()];
+        let chain = theme_chain("child", &dirs);
+        let index = index_theme("child", &dirs, 24);
+        let parent = index_theme("parent", &dirs, 24);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(chain, ["child", "parent", "hicolor"]);
+        assert_eq!(index["term"], (32, dir.join("child/apps/32/term.png")));
+        assert_eq!(index.len(), 1);
+        assert_eq!(parent["editor"].0, 48);
+    }
+
+    #[test]
+    fn prefers_the_smallest_size_which_fits() {
+        assert!(better(24, 32, 64));
+        assert!(better(24, 32, 16));
+        assert!(better(24, 16, 8));
+        assert!(!better(24, 16, 48));
+        assert!(!better(24, 32, 32));
+    }
+}
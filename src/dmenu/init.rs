@@ -1,17 +1,23 @@
-use libc::{c_char, c_int, isatty};
-use std::{ffi::CStr, mem::MaybeUninit, ptr};
+use libc::{c_int, c_void, isatty};
+use std::{collections::HashMap, ffi::CString, mem::MaybeUninit, ptr, slice};
 use x11::xft::{XftColor, XftColorAllocName};
 use x11::xlib::{
-    CapButt, Display, JoinMiter, LineSolid, Window, XCreateGC, XCreatePixmap, XDefaultColormap,
-    XDefaultDepth, XDefaultVisual, XSetLineAttributes, XWindowAttributes,
+    AllocNone, CapButt, Colormap, Display, JoinMiter, LineSolid, TrueColor, Visual,
+    VisualClassMask, VisualDepthMask, VisualScreenMask, Window, XCreateColormap, XCreateGC,
+    XCreatePixmap, XDefaultColormap, XDefaultDepth, XDefaultVisual, XFree, XGetVisualInfo,
+    XSetLineAttributes, XVisualInfo, XWindowAttributes,
 };
+use x11::xrender::{PictTypeDirect, XRenderFindVisualFormat};
 
-use crate::config::{Config, Schemes::*};
+use crate::config::Config;
 use crate::drw::Drw;
 use crate::fnt::*;
 use crate::globals::*;
-use crate::item::Items;
+use crate::history::History;
+use crate::icons::IconCache;
+use crate::item::{Items, Preselect};
 use crate::result::*;
+use crate::stream::StdinStream;
 use crate::util::*;
 
 impl Drw {
@@ -24,14 +30,26 @@ impl Drw {
         config: Config,
     ) -> CompResult<Self> {
         unsafe {
-            let drawable = XCreatePixmap(
-                dpy,
-                root,
-                wa.width as u32,
-                wa.height as u32,
-                XDefaultDepth(dpy, screen) as u32,
-            );
-            let gc = XCreateGC(dpy, root, 0, ptr::null_mut());
+            let argb = if config.argb {
+                let visual = argb_visual(dpy, screen, root);
+                if visual.is_none() {
+                    eprintln!("--argb: no 32 bit visual available, using the default");
+                }
+                visual
+            } else {
+                None
+            };
+            let (visual, depth, cmap) = argb.unwrap_or_else(|| {
+                (
+                    XDefaultVisual(dpy, screen),
+                    XDefaultDepth(dpy, screen),
+                    XDefaultColormap(dpy, screen),
+                )
+            });
+            let drawable =
+                XCreatePixmap(dpy, root, wa.width as u32, wa.height as u32, depth as u32);
+            // created on the pixmap, as its depth might not be the root window's
+            let gc = XCreateGC(dpy, drawable, 0, ptr::null_mut());
             XSetLineAttributes(dpy, gc, 1, LineSolid, CapButt, JoinMiter);
             let mut ret = Self {
                 wa,
@@ -40,6 +58,9 @@ impl Drw {
                 root,
                 drawable,
                 gc,
+                visual,
+                depth,
+                cmap,
                 fonts: Vec::new(),
                 pseudo_globals,
                 config,
@@ -48,15 +69,34 @@ impl Drw {
                 h: 0,
                 input: "".to_string(),
                 items: None,
+                stream: None,
+                history: None,
+                icons: IconCache::default(),
+                style_colors: HashMap::new(),
             };
 
+            if let Some(path) = &ret.config.history {
+                ret.history = Some(History::load(path)?);
+            }
+
             ret.fontset_create()?;
-            ret.pseudo_globals.lrpad = ret.fonts[0].height as i32;
+            ret.pseudo_globals.lrpad = match ret.config.hpad {
+                Some(hpad) => hpad as i32 * 2,
+                None => ret.fonts[0].height as i32,
+            };
+            // needed before reading stdin, to make room for --icons
+            ret.pseudo_globals.bh = (ret.fonts.iter().map(|f| f.height).max().unwrap()
+                + ret.config.vpad * 2)
+                .max(ret.config.render_minheight);
 
             ret.items = if ret.config.nostdin {
                 ret.format_stdin(vec![])?;
                 grabkeyboard(ret.dpy, ret.config.embed)?;
                 Some(Items::new(Vec::new()))
+            } else if ret.config.stream {
+                grabkeyboard(ret.dpy, ret.config.embed)?;
+                ret.stream = Some(StdinStream::spawn()?);
+                Some(Items::new(Vec::new()))
             } else {
                 Some(Items::new(if ret.config.fast && isatty(0) == 0 {
                     grabkeyboard(ret.dpy, ret.config.embed)?;
@@ -68,17 +108,28 @@ impl Drw {
                 }))
             };
 
-            for j in 0..SchemeLast as usize {
-                ret.pseudo_globals.schemeset[j] = ret.scm_create(ret.config.colors[j])?;
+            ret.items.as_mut().unwrap().preselect = if let Some(text) = &ret.config.select {
+                Some(Preselect::Output(text.clone()))
+            } else {
+                ret.config.select_index.map(Preselect::Index)
+            };
+
+            let colors = ret.config.resolved_colors();
+            for (scheme, clrnames) in colors.iter().enumerate() {
+                ret.pseudo_globals.schemeset[scheme] = ret.scm_create(clrnames)?;
             }
 
-            ret.config.lines = ret.config.lines.min(ret.get_items().len() as u32);
+            if !ret.config.stream {
+                // when streaming, there's no telling how many items there will be
+                let rows = (ret.get_items().len() as u32).div_ceil(ret.config.columns);
+                ret.config.lines = ret.config.lines.min(rows);
+            }
 
             Ok(ret)
         }
     }
 
-    fn scm_create(&self, clrnames: [[u8; 8]; 2]) -> CompResult<[*mut XftColor; 2]> {
+    fn scm_create(&self, clrnames: &[String; 2]) -> CompResult<[*mut XftColor; 2]> {
         let blank_val_1 = MaybeUninit::<XftColor>::uninit();
         let blank_val_2 = MaybeUninit::<XftColor>::uninit();
         let ret: [*mut XftColor; 2] = unsafe {
@@ -87,26 +138,30 @@ impl Drw {
                 Box::into_raw(Box::new(blank_val_2.assume_init())),
             ]
         };
-        self.clr_create(ret[0], clrnames[0].as_ptr() as *const c_char)?;
-        self.clr_create(ret[1], clrnames[1].as_ptr() as *const c_char)?;
+        self.clr_create(ret[0], &clrnames[0])?;
+        self.clr_create(ret[1], &clrnames[1])?;
         Ok(ret)
     }
 
-    fn clr_create(&self, dest: *mut XftColor, clrname: *const c_char) -> CompResult<()> {
+    /// Anything Xlib can parse, such as names and `rgb:` colors, plus `#rrggbbaa`
+    pub fn clr_create(&self, dest: *mut XftColor, clrname: &str) -> CompResult<()> {
+        let (name, alpha) = match clrname.strip_prefix('#') {
+            Some(hex) if hex.len() == 8 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                (&clrname[..7], u16::from_str_radix(&hex[6..], 16).unwrap())
+            }
+            _ => (clrname, 0xff),
+        };
+        let cname = CString::new(name)
+            .map_err(|_| Die::Stderr(format!("error, cannot allocate color {:?}", clrname)))?;
         unsafe {
-            if XftColorAllocName(
-                self.dpy,
-                XDefaultVisual(self.dpy, self.screen),
-                XDefaultColormap(self.dpy, self.screen),
-                clrname,
-                dest,
-            ) == 0
-            {
+            if XftColorAllocName(self.dpy, self.visual, self.cmap, cname.as_ptr(), dest) == 0 {
                 Die::stderr(format!(
-                    "error, cannot allocate color {:?}",
-                    CStr::from_ptr(clrname)
+                    "error, cannot allocate color {:?}: expected a color name, \
+                     #rgb, #rrggbb, #rrggbbaa or rgb:rr/gg/bb",
+                    clrname
                 ))
             } else {
+                apply_alpha(&mut *dest, alpha, self.depth);
                 Ok(())
             }
         }
@@ -124,3 +179,98 @@ impl Drw {
         Ok(())
     }
 }
+
+/// Gives an allocated color the alpha from `#rrggbbaa`. Only the 32 bit visual of `--argb`
+/// has an alpha channel, so otherwise the color is left opaque, matching its pixel.
+fn apply_alpha(dest: &mut XftColor, alpha: u16, depth: c_int) {
+    if depth != 32 {
+        return;
+    }
+    // XRender, and so the compositor, expects alpha to be premultiplied
+    let color = &mut dest.color;
+    color.alpha = alpha * 0x101;
+    for channel in [&mut color.red, &mut color.green, &mut color.blue].iter_mut() {
+        **channel = (**channel as u32 * color.alpha as u32 / 0xffff) as u16;
+    }
+    // Xft leaves the alpha bits of the pixel empty, which is fully transparent
+    dest.pixel = (color.alpha as u64 >> 8) << 24
+        | (color.red as u64 >> 8) << 16
+        | (color.green as u64 >> 8) << 8
+        | color.blue as u64 >> 8;
+}
+
+/// A 32 bit TrueColor visual with an alpha channel, along with a colormap for it
+unsafe fn argb_visual(
+    dpy: *mut Display,
+    screen: c_int,
+    root: Window,
+) -> Option<(*mut Visual, c_int, Colormap)> {
+    let mut template = MaybeUninit::<XVisualInfo>::zeroed().assume_init();
+    template.screen = screen;
+    template.depth = 32;
+    template.class = TrueColor;
+    let mut count = 0;
+    let infos = XGetVisualInfo(
+        dpy,
+        VisualScreenMask | VisualDepthMask | VisualClassMask,
+        &mut template,
+        &mut count,
+    );
+    if infos.is_null() {
+        return None;
+    }
+    let visual = slice::from_raw_parts(infos, count as usize)
+        .iter()
+        .map(|info| info.visual)
+        .find(|visual| {
+            let format = XRenderFindVisualFormat(dpy, *visual);
+            !format.is_null()
+                && (*format).type_ == PictTypeDirect
+                && (*format).direct.alphaMask != 0
+        });
+    XFree(infos as *mut c_void);
+    visual.map(|visual| (visual, 32, XCreateColormap(dpy, root, visual, AllocNone)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11::xrender::XRenderColor;
+
+    fn white() -> XftColor {
+        XftColor {
+            pixel: 0xffffff,
+            color: XRenderColor {
+                red: 0xffff,
+                green: 0xffff,
+                blue: 0xffff,
+                alpha: 0xffff,
+            },
+        }
+    }
+
+    #[test]
+    fn alpha_needs_argb() {
+        let mut color = white();
+        apply_alpha(&mut color, 0x80, 24);
+        assert_eq!(color.pixel, 0xffffff);
+        assert_eq!(
+            (color.color.red, color.color.green, color.color.blue),
+            (0xffff, 0xffff, 0xffff)
+        );
+        assert_eq!(color.color.alpha, 0xffff);
+    }
+
+    #[test]
+    fn alpha_is_premultiplied() {
+        let mut color = white();
+        apply_alpha(&mut color, 0x80, 32);
+        assert_eq!(color.color.alpha, 0x8080);
+        assert_eq!(color.color.red, 0x8080);
+        assert_eq!(color.pixel, 0x80808080);
+
+        let mut color = white();
+        apply_alpha(&mut color, 0xff, 32);
+        assert_eq!(color.pixel, 0xffffffff);
+    }
+}
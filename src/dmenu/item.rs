@@ -1,9 +1,15 @@
-use crate::config::{DefaultWidth, Schemes::*};
+use crate::config::{Align, Config, DefaultWidth, FieldSpec, GridOrder, Schemes::*, Styling};
 use crate::drw::{Drw, TextOption::*};
+use crate::icons;
+use crate::markup::{self, Runs};
+use crate::normalize::Folded;
 use crate::result::*;
 
+use itertools::Itertools;
 use libc::c_int;
+use rayon::prelude::*;
 use regex::Regex;
+use std::ops::Range;
 
 #[allow(unused_imports)]
 pub enum MatchCode {
@@ -17,57 +23,224 @@ pub use MatchCode::*;
 pub enum Direction {
     Vertical,
     Horizontal,
+    Grid,
 }
 pub use Direction::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Item {
     // dmenu entry
-    pub text: String,
+    pub display: String, // drawn in the menu
+    pub search: String,  // matched against the input
+    pub output: String,  // disposed of when selected
     pub out: bool,
     pub width: c_int,
+    pub highlight: Vec<Range<usize>>, // byte ranges of search, drawn if it's what is displayed
+    pub index: Option<usize>,         // position in Items::data, if it came from stdin
+    pub folded: Option<Folded>,       // search with --normalize, if that changes it
+    pub icon: Option<String>,         // path or theme name, drawn with --icons
+    pub styles: Runs,                 // byte ranges of display, from --ansi or --markup
+}
+
+impl FieldSpec {
+    /// Byte ranges of the chosen fields of `line`
+    pub fn fields(&self, line: &str, delimiter: &str) -> Vec<Range<usize>> {
+        let mut start = 0;
+        line.split(delimiter)
+            .map(|field| {
+                let range = start..start + field.len();
+                start = range.end + delimiter.len();
+                range
+            })
+            .enumerate()
+            .filter(|(i, _)| self.0.iter().any(|range| range.contains(i)))
+            .map(|(_, field)| field)
+            .collect()
+    }
+    /// The chosen fields of `line`, joined back together with `delimiter`
+    pub fn select(&self, line: &str, delimiter: &str) -> String {
+        self.fields(line, delimiter)
+            .into_iter()
+            .map(|field| &line[field])
+            .join(delimiter)
+    }
 }
 
 impl Item {
+    /// An item which displays, searches and outputs the same text
+    #[allow(unused)] // for plugins
     pub fn new(text: String, out: bool, drw: &mut Drw) -> CompResult<Self> {
+        let mut item = Self::with_texts(text.clone(), text.clone(), text, drw)?;
+        item.out = out;
+        Ok(item)
+    }
+    fn with_texts(
+        display: String,
+        search: String,
+        output: String,
+        drw: &mut Drw,
+    ) -> CompResult<Self> {
         Ok(Self {
-            out,
-            width: drw.textw(Other(&text))?,
-            text,
+            out: false,
+            width: drw.textw(Other(&display))? + Self::iconw(drw),
+            folded: if drw.config.normalize {
+                Folded::new(&search)
+            } else {
+                Option::None
+            },
+            display,
+            search,
+            output,
+            highlight: Vec::new(),
+            index: Option::None,
+            icon: Option::None,
+            styles: Vec::new(),
         })
     }
-    pub fn draw(&self, x: c_int, y: c_int, w: c_int, drw: &mut Drw) -> CompResult<c_int> {
+    /// Space left for icons before the text, the same for every item so they line up
+    fn iconw(drw: &Drw) -> c_int {
+        if drw.config.icons {
+            drw.pseudo_globals.bh as c_int + drw.pseudo_globals.lrpad / 2
+        } else {
+            0
+        }
+    }
+    /// An item for a line of stdin, split into fields by `--delimiter`
+    /// for `--with_nth`, `--nth` and `--output_nth`.
+    /// With `--icons`, the icon is taken off the end first,
+    /// then any `--ansi` or `--markup` styling is taken out.
+    pub fn from_line(line: String, drw: &mut Drw) -> CompResult<Self> {
+        let (line, icon) = if drw.config.icons {
+            icons::split_line(line)
+        } else {
+            (line, Option::None)
+        };
+        let (line, runs) = match drw.config.styling {
+            Styling::Plain => (line, Vec::new()),
+            Styling::Ansi => markup::ansi(&line),
+            Styling::Markup => markup::markup(&line).unwrap_or((line, Vec::new())),
+        };
+        let (display, search, output) = Self::texts(&line, &drw.config);
+        let styles = match &drw.config.with_nth {
+            Some(spec) if !runs.is_empty() => markup::select(
+                &runs,
+                &spec.fields(&line, &drw.config.delimiter),
+                drw.config.delimiter.len(),
+            ),
+            _ => runs,
+        };
+        let mut item = Self::with_texts(display, search, output, drw)?;
+        item.icon = icon;
+        item.styles = styles;
+        Ok(item)
+    }
+    /// The display, search and output text of a line, as picked by `--with_nth`,
+    /// `--nth` and `--output_nth`. Search defaults to display, the rest to the whole line.
+    fn texts(line: &str, config: &Config) -> (String, String, String) {
+        let select = |spec: &Option<FieldSpec>| {
+            spec.as_ref()
+                .map(|spec| spec.select(line, &config.delimiter))
+        };
+        let display = select(&config.with_nth).unwrap_or_else(|| line.to_owned());
+        let search = select(&config.nth).unwrap_or_else(|| display.clone());
+        let output = select(&config.output_nth).unwrap_or_else(|| line.to_owned());
+        (display, search, output)
+    }
+    /// What the input is matched against: `search`, folded if `--normalize` is given
+    pub fn match_text(&self) -> &str {
+        self.folded
+            .as_ref()
+            .map_or(&self.search, |folded| &folded.text)
+    }
+    /// Highlight byte ranges of `match_text`
+    fn set_highlight(&mut self, ranges: Vec<Range<usize>>) {
+        self.highlight = if let Some(folded) = &self.folded {
+            ranges
+                .into_iter()
+                .map(|range| folded.unfold(&self.search, range))
+                .collect()
+        } else {
+            ranges
+        };
+    }
+    pub fn draw(&self, mut x: c_int, y: c_int, mut w: c_int, drw: &mut Drw) -> CompResult<c_int> {
+        if drw.config.icons {
+            let iconw = Self::iconw(drw).min(w);
+            drw.icon(self.icon.as_deref(), x, y, iconw);
+            x += iconw;
+            w -= iconw;
+        }
+        let lpad = drw.pseudo_globals.lrpad / 2;
+        let lpad = match drw.config.align {
+            Align::Left => lpad,
+            align => {
+                // text which doesn't fit is cut off on the right, as usual
+                let free = (w - drw.textw(Entry(self))?).max(0);
+                lpad + if align == Align::Center {
+                    free / 2
+                } else {
+                    free
+                }
+            }
+        };
         drw.text(
             x,
             y,
             w as u32,
             drw.pseudo_globals.bh as u32,
-            drw.pseudo_globals.lrpad as u32 / 2,
-            Other(&self.text),
+            lpad as u32,
+            Entry(self),
             false,
         )
         .map(|o| o.0)
     }
     #[allow(unused)] // won't be used if overriden
-    pub fn matches(&self, re: &Regex) -> MatchCode {
-        match re
-            .find_iter(&self.text)
-            .nth(0)
-            .map(|m| (m.start(), m.end()))
-            .unwrap_or((1, 0))
-        {
-            (1, 0) => MatchCode::None, // don't expect zero length matches...
-            (0, end) =>
-            //                unless search is empty
-            {
-                if end == self.text.len() {
-                    MatchCode::Exact
-                } else {
-                    MatchCode::Prefix
+    pub fn matches(&self, re: &Regex, tokens: &[Regex]) -> MatchCode {
+        let text = self.match_text();
+        // every space seperated token must be found somewhere in the item
+        if !tokens.iter().all(|token| token.is_match(text)) {
+            return MatchCode::None;
+        }
+        if let Some(m) = re.find(text) {
+            if m.start() == 0 && m.end() == text.len() {
+                return MatchCode::Exact;
+            }
+        }
+        // otherwise, rank on the first token like dmenu does
+        match tokens.first().and_then(|token| token.find(text)) {
+            Some(m) if m.start() > 0 => MatchCode::Substring,
+            _ => MatchCode::Prefix, // empty input matches everything
+        }
+    }
+    /// Clone this item, highlighting wherever the tokens are found
+    #[allow(unused)] // won't be used if overriden
+    pub fn highlighted(&self, tokens: &[Regex]) -> Self {
+        let mut item = self.clone();
+        item.set_highlight(
+            tokens
+                .iter()
+                .filter_map(|token| token.find(self.match_text()))
+                .map(|m| m.range())
+                .collect(),
+        );
+        item
+    }
+    /// Highlight the chars of `match_text` at the given char (not byte) indices,
+    /// such as the ones given by fuzzy matchers. Indices must be sorted.
+    #[allow(unused)] // for plugins
+    pub fn highlight_chars(&mut self, indices: &[usize]) {
+        let mut wanted = indices.iter().peekable();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (i, (byte, c)) in self.match_text().char_indices().enumerate() {
+            while wanted.next_if(|w| **w < i).is_some() {}
+            if wanted.peek() == Some(&&i) {
+                match ranges.last_mut() {
+                    Some(range) if range.end == byte => range.end += c.len_utf8(),
+                    _ => ranges.push(byte..byte + c.len_utf8()),
                 }
             }
-            _ => MatchCode::Substring,
         }
+        self.set_highlight(ranges);
     }
 }
 
@@ -108,28 +281,141 @@ impl std::ops::Index<usize> for Partition {
     }
 }
 
+#[derive(Debug)]
+pub struct MatchCache {
+    pub input: String,
+    pub indices: Vec<usize>, // into Items::data, sorted
+    pub scanned: usize,      // how many items there were, later ones are still unchecked
+}
+
+/// Something which can be clicked on in the menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Match(usize), // a value for Items::curr
+    PrevPage,
+    NextPage,
+}
+
+/// Where a `Target` was last drawn
+#[derive(Debug)]
+pub struct Hitbox {
+    pub x: c_int,
+    pub y: c_int,
+    pub w: c_int,
+    pub h: c_int,
+    pub target: Target,
+}
+
+/// An item to select once it's matched, from `--select` or `--select_index`
+#[derive(Debug)]
+pub enum Preselect {
+    Index(usize),
+    Output(String),
+}
+
 #[derive(Debug)]
 pub struct Items {
     pub data: Vec<Item>,
     pub cached_partitions: Vec<Partition>, // seperated into screens
     pub curr: usize,
+    pub match_cache: Option<MatchCache>, // last input and what it matched
+    pub preselect: Option<Preselect>,
+    pub hitboxes: Vec<Hitbox>, // of the partition on screen
 }
 
 impl Items {
     pub fn new(data: Vec<Item>) -> Self {
-        Self {
-            data,
+        let mut items = Self {
+            data: Vec::new(),
             cached_partitions: Vec::new(),
             curr: 0,
+            match_cache: Option::None, // MatchCode::None is in scope
+            preselect: Option::None,
+            hitboxes: Vec::new(),
+        };
+        items.extend(data);
+        items
+    }
+    /// Add items read from stdin
+    pub fn extend(&mut self, items: Vec<Item>) {
+        let start = self.data.len();
+        self.data
+            .extend(items.into_iter().enumerate().map(|(i, mut item)| {
+                item.index = Some(start + i);
+                item
+            }));
+    }
+    /// Stdin indices and outputs of the items marked with `--multi`, in stdin order
+    pub fn marked(&self) -> Vec<(Option<usize>, String)> {
+        self.data
+            .iter()
+            .filter(|item| item.out)
+            .map(|item| (item.index, item.output.clone()))
+            .collect()
+    }
+    /// Moves the selection onto the preselected item, wherever it ended up in `matches`.
+    /// This keeps up with every re-match, as the item may not have been read from stdin
+    /// yet or may be moved by new lines and `--history`, until the user moves the
+    /// selection or edits the input.
+    pub fn apply_preselect(&mut self, matches: &[Item]) {
+        if let Some(wanted) = &self.preselect {
+            let found = matches.iter().position(|item| match wanted {
+                Preselect::Index(index) => item.index == Some(*index),
+                Preselect::Output(output) => item.output == *output,
+            });
+            if let Some(position) = found {
+                self.curr = position;
+            }
         }
     }
     pub fn match_len(&self) -> usize {
         self.cached_partitions.len()
     }
-    pub fn draw(drw: &mut Drw, direction: Direction) -> CompResult<bool> {
-        // gets an apropriate vec of matches
-        let pre_processed_items = drw.gen_matches()?;
-        let items_to_draw = drw.postprocess_matches(pre_processed_items)?;
+    /// Indices into `data` which could match `input`, in stdin order.
+    /// If `input` just extends the last matched input, nothing the last input
+    /// missed can match now, so only its matches (and newly read items) need
+    /// to be checked again.
+    pub fn candidates(&self, input: &str) -> Vec<usize> {
+        match &self.match_cache {
+            Some(cache) if input.starts_with(&cache.input) => cache
+                .indices
+                .iter()
+                .copied()
+                .chain(cache.scanned..self.data.len())
+                .collect(),
+            _ => (0..self.data.len()).collect(),
+        }
+    }
+    /// Remember what `input` matched, for narrowing down later
+    pub fn cache_matches(&mut self, input: String, mut indices: Vec<usize>) {
+        indices.sort_unstable();
+        self.match_cache = Some(MatchCache {
+            input,
+            indices,
+            scanned: self.data.len(),
+        });
+    }
+    /// Runs `f` over the `candidates`, keeping the indices of those which produce a value.
+    /// Once there are at least `threshold` candidates this is split across every core;
+    /// either way the results stay in the order of `candidates`.
+    pub fn filter_candidates<T, F>(
+        &self,
+        candidates: Vec<usize>,
+        threshold: usize,
+        f: F,
+    ) -> Vec<(usize, T)>
+    where
+        T: Send,
+        F: Fn(&Item) -> Option<T> + Sync,
+    {
+        let apply = |index: usize| f(&self.data[index]).map(|value| (index, value));
+        if candidates.len() >= threshold {
+            candidates.into_par_iter().filter_map(apply).collect()
+        } else {
+            candidates.into_iter().filter_map(apply).collect()
+        }
+    }
+    pub fn draw(drw: &mut Drw, items_to_draw: Vec<Item>, direction: Direction) -> CompResult<bool> {
         let rangle = ">".to_string();
         let rangle_width = drw.textw(Other(&rangle))?;
         let langle = "<".to_string();
@@ -149,8 +435,10 @@ impl Items {
             DefaultWidth::Max => {
                 let curr = drw.items.as_ref().unwrap().curr;
                 let data = drw.get_items();
-                let mut w = drw.w - drw.pseudo_globals.promptw - data[curr].width;
-                if curr < data.len() - 1 {
+                let mut w = drw.w
+                    - drw.pseudo_globals.promptw
+                    - data.get(curr).map_or(0, |item| item.width);
+                if curr + 1 < data.len() {
                     w -= rangle_width;
                 }
                 if curr > 0 {
@@ -177,10 +465,15 @@ impl Items {
 
         if matched_partitions.len() == 0 {
             drw.items.as_mut().unwrap().cached_partitions = matched_partitions;
+            drw.items.as_mut().unwrap().hitboxes.clear();
             return Ok(false); // nothing to draw
         }
 
         let (partition_i, partition) = Partition::decompose(&matched_partitions, drw);
+        let first = drw.items.as_ref().unwrap().curr - partition_i; // of this partition
+        let bh = drw.pseudo_globals.bh as c_int;
+        let pitch = bh + drw.config.line_spacing as c_int; // of rows below the input
+        let mut hitboxes = Vec::new();
 
         let mut coord = match direction {
             Horizontal => {
@@ -190,7 +483,7 @@ impl Items {
                     0
                 }
             }
-            Vertical => drw.pseudo_globals.bh as c_int,
+            Vertical | Grid => pitch,
         };
 
         if let Horizontal = direction {
@@ -211,6 +504,13 @@ impl Items {
             if partition > 0 {
                 // draw langle if required
                 drw.setscheme(SchemeNorm);
+                hitboxes.push(Hitbox {
+                    x: coord,
+                    y: 0,
+                    w: langle_width,
+                    h: bh,
+                    target: Target::PrevPage,
+                });
                 coord = drw
                     .text(
                         coord,
@@ -243,6 +543,7 @@ impl Items {
             } else {
                 drw.setscheme(SchemeNorm);
             }
+            let start = coord;
             match direction {
                 Horizontal => {
                     if partition + 1 < matched_partitions.len() {
@@ -276,15 +577,65 @@ impl Items {
                             drw,
                         )?;
                     }
+                    hitboxes.push(Hitbox {
+                        x: start,
+                        y: 0,
+                        w: coord - start,
+                        h: bh,
+                        target: Target::Match(first + index),
+                    });
                 }
                 Vertical => {
                     matched_partitions[partition][index].draw(0, coord, drw.w, drw)?;
-                    coord += drw.pseudo_globals.bh as i32;
+                    coord += pitch;
+                    hitboxes.push(Hitbox {
+                        x: 0,
+                        y: start,
+                        w: drw.w,
+                        h: pitch, // no gaps between rows to click in
+                        target: Target::Match(first + index),
+                    });
+                }
+                Grid => {
+                    let (lines, columns) = (drw.config.lines as usize, drw.config.columns as usize);
+                    let (row, column) = match drw.config.grid_order {
+                        GridOrder::Columns => (index % lines, index / lines),
+                        GridOrder::Rows => (index / columns, index % columns),
+                    };
+                    let cellw = drw.w / columns as c_int;
+                    let x = column as c_int * cellw;
+                    let y = coord + row as c_int * pitch;
+                    // the last column takes what's left after rounding
+                    let w = if column + 1 == columns {
+                        drw.w - x
+                    } else {
+                        cellw
+                    };
+                    matched_partitions[partition][index].draw(x, y, w, drw)?;
+                    hitboxes.push(Hitbox {
+                        x,
+                        y,
+                        w,
+                        h: pitch,
+                        target: Target::Match(first + index),
+                    });
                 }
             }
         }
+        if let Horizontal = direction {
+            if partition + 1 < matched_partitions.len() {
+                hitboxes.push(Hitbox {
+                    x: drw.w - rangle_width,
+                    y: 0,
+                    w: rangle_width,
+                    h: bh,
+                    target: Target::NextPage,
+                });
+            }
+        }
 
         drw.items.as_mut().unwrap().cached_partitions = matched_partitions;
+        drw.items.as_mut().unwrap().hitboxes = hitboxes;
 
         Ok(true)
     }
@@ -355,6 +706,10 @@ impl Items {
                 .chunks(drw.config.lines as usize)
                 .map(|p| Partition::new(p.to_vec(), 0))
                 .collect()),
+            Grid => Ok(input
+                .chunks((drw.config.lines * drw.config.columns) as usize)
+                .map(|p| Partition::new(p.to_vec(), 0))
+                .collect()),
         }
     }
 }
@@ -370,3 +725,109 @@ impl Drw {
         &mut self.items.as_mut().unwrap().data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(search: &str) -> Item {
+        Item {
+            display: search.to_owned(),
+            search: search.to_owned(),
+            output: search.to_owned(),
+            out: false,
+            width: 0,
+            highlight: Vec::new(),
+            index: Option::None,
+            folded: Option::None,
+            icon: Option::None,
+            styles: Vec::new(),
+        }
+    }
+
+    /// Matches `search` against `input` like the default substring matcher
+    fn matches(search: &str, input: &str) -> MatchCode {
+        let re = Regex::new(&regex::escape(input)).unwrap();
+        let tokens: Vec<Regex> = input
+            .split_whitespace()
+            .map(|token| Regex::new(&regex::escape(token)).unwrap())
+            .collect();
+        item(search).matches(&re, &tokens)
+    }
+
+    #[test]
+    fn every_token_must_match() {
+        assert!(matches!(matches("firefox nightly", "fire night"), Prefix));
+        assert!(matches!(
+            matches("firefox nightly", "night fire"),
+            Substring
+        ));
+        assert!(matches!(
+            matches("firefox nightly", "fire beta"),
+            MatchCode::None
+        ));
+        assert!(matches!(matches("firefox", "fire fox"), Prefix));
+    }
+
+    #[test]
+    fn tiers() {
+        assert!(matches!(matches("term", "term"), Exact));
+        assert!(matches!(matches("terminal", "term"), Prefix));
+        assert!(matches!(matches("xterm", "term"), Substring));
+        assert!(matches!(matches("xterm", ""), Prefix));
+    }
+
+    #[test]
+    fn highlights_each_token() {
+        let tokens = [Regex::new("fire").unwrap(), Regex::new("night").unwrap()];
+        let item = item("firefox nightly").highlighted(&tokens);
+        assert_eq!(item.highlight, vec![0..4, 8..13]);
+    }
+
+    #[test]
+    fn field_ranges() {
+        let spec = FieldSpec(vec![0..=0, 2..=usize::MAX]);
+        assert_eq!(spec.fields("a:bb:ccc:d", ":"), vec![0..1, 5..8, 9..10]);
+        assert_eq!(spec.select("a:bb:ccc:d", ":"), "a:ccc:d");
+        // a multi byte delimiter, and empty fields
+        assert_eq!(spec.fields("a, , c", ", "), vec![0..1, 5..6]);
+        assert_eq!(spec.select("a::c", ":"), "a:c");
+    }
+
+    #[test]
+    fn fields_for_each_use() {
+        let config = Config {
+            delimiter: "\t".to_owned(),
+            with_nth: Some(FieldSpec(vec![1..=1])),
+            output_nth: Some(FieldSpec(vec![0..=0])),
+            ..Config::default()
+        };
+        let texts = Item::texts("id-7\tFirefox\tweb browser", &config);
+        assert_eq!(
+            texts,
+            (
+                "Firefox".to_owned(),
+                "Firefox".to_owned(),
+                "id-7".to_owned()
+            )
+        );
+        let config = Config {
+            nth: Some(FieldSpec(vec![2..=2])),
+            with_nth: Option::None,
+            ..config
+        };
+        let (display, search, output) = Item::texts("id-7\tFirefox\tweb browser", &config);
+        assert_eq!(display, "id-7\tFirefox\tweb browser");
+        assert_eq!(search, "web browser");
+        assert_eq!(output, "id-7");
+    }
+
+    #[test]
+    fn missing_fields() {
+        let spec = FieldSpec(vec![1..=1, 4..=5]);
+        assert_eq!(spec.select("a b c", " "), "b");
+        assert_eq!(spec.select("no delimiter", "\t"), "");
+        let spec = FieldSpec(vec![0..=1]);
+        assert_eq!(spec.select("no delimiter", "\t"), "no delimiter");
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use x11::xlib::{
+    ControlMask, Mod1Mask, Mod4Mask, NoSymbol, ShiftMask, XConvertCase, XStringToKeysym,
+};
+
+/// Modifiers which are part of a binding. Others, like num lock, are ignored.
+const MODS: u32 = ShiftMask | ControlMask | Mod1Mask | Mod4Mask;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    sym: u32,
+    mods: u32,
+}
+
+impl Key {
+    pub fn new(sym: u32, mods: u32) -> Self {
+        Self {
+            sym,
+            mods: mods & MODS,
+        }
+    }
+    pub fn plain(sym: u32) -> Self {
+        Self::new(sym, 0)
+    }
+    pub fn ctrl(sym: u32) -> Self {
+        Self::new(sym, ControlMask)
+    }
+    pub fn alt(sym: u32) -> Self {
+        Self::new(sym, Mod1Mask)
+    }
+    pub fn shift(sym: u32) -> Self {
+        Self::new(sym, ShiftMask)
+    }
+
+    /// Parses keys such as `C-a`, `M-S-Return` or `Super-space`.
+    /// The key itself is an X keysym name, as listed by `xev`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut mods = 0;
+        let mut rest = spec;
+        while let Some(dash) = rest.find('-') {
+            mods |= match &rest[..dash] {
+                "C" => ControlMask,
+                "M" | "A" => Mod1Mask,
+                "S" => ShiftMask,
+                "Super" => Mod4Mask,
+                _ => break, // part of the keysym name
+            };
+            rest = &rest[dash + 1..];
+        }
+        let name = CString::new(rest).map_err(|_| format!("invalid key `{}`", spec))?;
+        let mut sym = unsafe { XStringToKeysym(name.as_ptr()) };
+        if sym == NoSymbol as u64 {
+            return Err(format!("unknown key `{}`", rest));
+        }
+        if mods & ShiftMask != 0 {
+            // shift turns `y` into `Y` before we ever see it
+            let (mut lower, mut upper) = (0, 0);
+            unsafe { XConvertCase(sym, &mut lower, &mut upper) };
+            sym = upper;
+        }
+        Ok(Self::new(sym as u32, mods))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Cancel,
+    Accept,
+    AcceptCustom,
+    AcceptKeepOpen,
+    AcceptCustomKeepOpen,
+    Complete,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    WordLeft,
+    WordRight,
+    DeleteBack,
+    DeleteForward,
+    DeleteWordBack,
+    DeleteWordForward,
+    DeleteToStart,
+    DeleteToEnd,
+    Paste,
+    ToggleMark,
+    CycleMatch,
+}
+
+/// Names used by `--bind` and `--keymap`
+const ACTIONS: [(&str, Action); 25] = [
+    ("cancel", Action::Cancel),
+    ("accept", Action::Accept),
+    ("accept-custom", Action::AcceptCustom),
+    ("accept-keep-open", Action::AcceptKeepOpen),
+    ("accept-custom-keep-open", Action::AcceptCustomKeepOpen),
+    ("complete", Action::Complete),
+    ("first", Action::First),
+    ("last", Action::Last),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("move-up", Action::MoveUp),
+    ("move-down", Action::MoveDown),
+    ("move-left", Action::MoveLeft),
+    ("move-right", Action::MoveRight),
+    ("word-left", Action::WordLeft),
+    ("word-right", Action::WordRight),
+    ("delete-back", Action::DeleteBack),
+    ("delete-forward", Action::DeleteForward),
+    ("delete-word-back", Action::DeleteWordBack),
+    ("delete-word-forward", Action::DeleteWordForward),
+    ("delete-to-start", Action::DeleteToStart),
+    ("delete-to-end", Action::DeleteToEnd),
+    ("paste", Action::Paste),
+    ("toggle-mark", Action::ToggleMark),
+    ("cycle-match", Action::CycleMatch),
+];
+
+#[derive(Debug, Clone)]
+pub struct Keymap(HashMap<Key, Action>);
+
+impl Keymap {
+    pub fn new(bindings: Vec<(Key, Action)>) -> Self {
+        Self(bindings.into_iter().collect())
+    }
+
+    /// Adds a `KEY:ACTION` binding, replacing whatever the key did before.
+    /// The action `none` unbinds the key.
+    pub fn bind(&mut self, binding: &str) -> Result<(), String> {
+        let (key, action) = match binding.rfind(':') {
+            Some(i) if i > 0 => (&binding[..i], binding[i + 1..].trim()),
+            _ => return Err(format!("expected KEY:ACTION, got `{}`", binding)),
+        };
+        let key = Key::parse(key.trim())?;
+        if action == "none" {
+            self.0.remove(&key);
+            return Ok(());
+        }
+        let action = ACTIONS
+            .iter()
+            .find(|(name, _)| *name == action)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| format!("unknown action `{}`", action))?;
+        self.0.insert(key, action);
+        Ok(())
+    }
+
+    /// The action for a key press, if any. Shift is needed to type some keys,
+    /// such as `Y`, so bindings like `C-Y` also match without an explicit `S-`.
+    pub fn lookup(&self, sym: u32, state: u32) -> Option<Action> {
+        let key = Key::new(sym, state);
+        self.0.get(&key).copied().or_else(|| {
+            if key.mods & ShiftMask != 0 {
+                self.0.get(&Key::new(sym, key.mods & !ShiftMask)).copied()
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -1,16 +1,26 @@
 mod additional_bindings;
 mod clapflags;
 mod config;
+mod config_file;
 mod drw;
 mod fnt;
 mod globals;
+mod history;
+mod icons;
 mod init;
 mod item;
+mod keys;
+mod markup;
+mod matching;
+mod mouse;
+mod normalize;
 mod plugin_entry;
 mod result;
 mod run;
 mod setup;
+mod stream;
 mod util;
+mod xresources;
 mod plugins {
     include!(concat!(env!("OUT_DIR"), "/proc_mod_plugin.rs"));
 }
@@ -60,6 +70,7 @@ fn try_main() -> CompResult<()> {
         if dpy == ptr::null_mut() {
             return Die::stderr("cannot open display".to_owned());
         }
+        xresources::load(dpy, &mut config)?;
         let screen = XDefaultScreen(dpy);
         let root = XRootWindow(dpy, screen);
         let parentwin = root.max(config.embed);
@@ -0,0 +1,337 @@
+use std::ops::Range;
+
+/// How a run of text is drawn, from `--ansi` or `--markup`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<String>, // anything clr_create takes
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+/// A byte range of some text, with how it's drawn
+pub type Run = (Range<usize>, Style);
+/// Runs of some text, leaving out what's unstyled
+pub type Runs = Vec<Run>;
+
+/// Builds plain text and its runs, as markup is taken out
+#[derive(Default)]
+struct Styler {
+    text: String,
+    runs: Runs,
+    style: Style,
+}
+
+impl Styler {
+    fn push(&mut self, text: &str) {
+        let range = self.text.len()..self.text.len() + text.len();
+        self.text.push_str(text);
+        if text.is_empty() || self.style == Style::default() {
+            return;
+        }
+        match self.runs.last_mut() {
+            Some((last, style)) if last.end == range.start && *style == self.style => {
+                last.end = range.end
+            }
+            _ => self.runs.push((range, self.style.clone())),
+        }
+    }
+}
+
+/// The xterm colors, for SGR 30-37, 90-97 and the start of the 256 color palette
+const BASE_COLORS: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+fn palette(n: u32) -> Option<String> {
+    let rgb = |r, g, b| format!("#{:02x}{:02x}{:02x}", r, g, b);
+    match n {
+        0..=15 => Some(BASE_COLORS[n as usize].to_owned()),
+        16..=231 => {
+            let level = |i: u32| if i == 0 { 0 } else { 55 + i * 40 };
+            let n = n - 16;
+            Some(rgb(level(n / 36), level(n / 6 % 6), level(n % 6)))
+        }
+        232..=255 => {
+            let gray = 8 + (n - 232) * 10;
+            Some(rgb(gray, gray, gray))
+        }
+        _ => None,
+    }
+}
+
+/// A 256 color (`5;N`) or truecolor (`2;R;G;B`) argument of SGR 38 and 48
+fn extended_color(params: &mut impl Iterator<Item = u32>) -> Option<String> {
+    match params.next()? {
+        5 => palette(params.next()?),
+        2 => {
+            let (r, g, b) = (params.next()?, params.next()?, params.next()?);
+            Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                r.min(255),
+                g.min(255),
+                b.min(255)
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn sgr(params: &str, style: &mut Style) {
+    let mut params = params.split(';').map(|p| p.parse::<u32>().unwrap_or(0));
+    while let Some(param) = params.next() {
+        match param {
+            0 => *style = Style::default(),
+            1 => style.bold = true,
+            22 => style.bold = false,
+            4 => style.underline = true,
+            24 => style.underline = false,
+            30..=37 => style.fg = palette(param - 30),
+            90..=97 => style.fg = palette(param - 90 + 8),
+            38 => style.fg = extended_color(&mut params),
+            39 => style.fg = None,
+            40..=47 => style.bg = palette(param - 40),
+            100..=107 => style.bg = palette(param - 100 + 8),
+            48 => style.bg = extended_color(&mut params),
+            49 => style.bg = None,
+            _ => {} // italics, blinking and so on are dropped
+        }
+    }
+}
+
+/// Takes ANSI escape sequences out of `line`, keeping the colors, bold and
+/// underline set by SGR (`\e[...m`) sequences
+pub fn ansi(line: &str) -> (String, Runs) {
+    let mut styler = Styler::default();
+    let mut rest = line;
+    while let Some(esc) = rest.find('\x1b') {
+        styler.push(&rest[..esc]);
+        rest = &rest[esc + 1..];
+        if let Some(csi) = rest.strip_prefix('[') {
+            // parameters, then a final byte from @ to ~
+            let end = csi.find(|c| ('@'..='~').contains(&c)).unwrap_or(csi.len());
+            if csi[end..].starts_with('m') {
+                sgr(&csi[..end], &mut styler.style);
+            }
+            rest = &csi[(end + 1).min(csi.len())..];
+        } else if let Some(osc) = rest.strip_prefix(']') {
+            // such as hyperlinks from ls, ended by BEL or ST
+            rest = match osc.find(['\x07', '\x1b']) {
+                Some(end) if osc[end..].starts_with('\x07') => &osc[end + 1..],
+                Some(end) => osc[end + 1..].strip_prefix('\\').unwrap_or(&osc[end + 1..]),
+                None => "",
+            };
+        } else {
+            // intermediate bytes, like the `(` of `\e(B` from `tput sgr0`, then a final byte
+            rest = rest.trim_start_matches(|c| (' '..='/').contains(&c));
+            rest = &rest[rest.chars().next().map_or(0, char::len_utf8)..];
+        }
+    }
+    styler.push(rest);
+    (styler.text, styler.runs)
+}
+
+fn span(attributes: &str, style: &mut Style) -> Option<()> {
+    let mut rest = attributes.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (value, after) = value[1..].split_once(quote)?;
+        match key.trim() {
+            "foreground" | "fgcolor" | "color" => style.fg = Some(value.to_owned()),
+            "background" | "bgcolor" => style.bg = Some(value.to_owned()),
+            "weight" => style.bold = matches!(value, "bold" | "ultrabold" | "heavy"),
+            "underline" => style.underline = value != "none",
+            _ => return None,
+        }
+        rest = after.trim_start();
+    }
+    Some(())
+}
+
+/// Takes Pango-like markup out of `line`: `<b>`, `<u>` and `<span>` with
+/// `foreground`, `background`, `weight` and `underline`, plus `&amp;` and friends.
+/// Returns None if the markup is malformed.
+pub fn markup(line: &str) -> Option<(String, Runs)> {
+    let mut styler = Styler::default();
+    let mut open: Vec<(&str, Style)> = Vec::new(); // tags, with the style from before each
+    let mut rest = line;
+    while let Some(next) = rest.find(['<', '&']) {
+        styler.push(&rest[..next]);
+        rest = &rest[next..];
+        if rest.starts_with('&') {
+            let end = rest.find(';')?;
+            styler.push(match &rest[1..end] {
+                "amp" => "&",
+                "lt" => "<",
+                "gt" => ">",
+                "quot" => "\"",
+                "apos" => "'",
+                _ => return None,
+            });
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest.find('>')?;
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                let (opened, style) = open.pop()?;
+                if opened != name.trim() {
+                    return None;
+                }
+                styler.style = style;
+            } else {
+                let (name, attributes) = tag.split_once(' ').unwrap_or((tag, ""));
+                open.push((name, styler.style.clone()));
+                match name {
+                    "b" => styler.style.bold = true,
+                    "u" => styler.style.underline = true,
+                    "span" => span(attributes, &mut styler.style)?,
+                    _ => return None,
+                }
+            }
+        }
+    }
+    styler.push(rest);
+    if open.is_empty() {
+        Some((styler.text, styler.runs))
+    } else {
+        None
+    }
+}
+
+/// Runs of `fields` (byte ranges of the original text) once they're joined
+/// together with a `delimiter_len` long delimiter, as `--with_nth` does
+pub fn select(runs: &Runs, fields: &[Range<usize>], delimiter_len: usize) -> Runs {
+    let mut selected = Runs::new();
+    let mut offset = 0;
+    for field in fields {
+        for (range, style) in runs {
+            let (start, end) = (range.start.max(field.start), range.end.min(field.end));
+            if start < end {
+                let start = start - field.start + offset;
+                selected.push((start..end - field.start + offset, style.clone()));
+            }
+        }
+        offset += field.len() + delimiter_len;
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fg(color: &str) -> Style {
+        Style {
+            fg: Some(color.to_owned()),
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn sgr_colors() {
+        let (text, runs) = ansi("\x1b[31mred\x1b[0m plain \x1b[1;94mbold\x1b[m");
+        assert_eq!(text, "red plain bold");
+        let bold_blue = Style {
+            bold: true,
+            ..fg("#5c5cff")
+        };
+        assert_eq!(runs, vec![(0..3, fg("#cd0000")), (10..14, bold_blue)]);
+    }
+
+    #[test]
+    fn sgr_extended_colors() {
+        let (_, runs) = ansi("\x1b[38;5;196ma\x1b[38;5;244mb\x1b[38;2;1;2;300mc\x1b[48;5;16md");
+        assert_eq!(
+            runs,
+            vec![
+                (0..1, fg("#ff0000")),
+                (1..2, fg("#808080")),
+                (2..3, fg("#0102ff")),
+                (
+                    3..4,
+                    Style {
+                        bg: Some("#000000".to_owned()),
+                        ..fg("#0102ff")
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sgr_merges_and_resets() {
+        let (_, runs) = ansi("\x1b[4mab\x1b[31m\x1b[39mcd\x1b[24mef");
+        let underline = Style {
+            underline: true,
+            ..Style::default()
+        };
+        assert_eq!(runs, vec![(0..4, underline)]);
+    }
+
+    #[test]
+    fn other_escapes_are_dropped() {
+        let link = "\x1b]8;;file:///tmp\x1b\\tmp\x1b]8;;\x07";
+        assert_eq!(ansi(link), ("tmp".to_owned(), Vec::new()));
+        assert_eq!(ansi("\x1b[2Ka\x1b(Bb\x1b[").0, "ab");
+    }
+
+    #[test]
+    fn markup_tags() {
+        let (text, runs) =
+            markup("<b>bold</b> &amp; <span foreground='#fff' underline=\"single\">x</span>")
+                .unwrap();
+        assert_eq!(text, "bold & x");
+        let bold = Style {
+            bold: true,
+            ..Style::default()
+        };
+        let span = Style {
+            underline: true,
+            ..fg("#fff")
+        };
+        assert_eq!(runs, vec![(0..4, bold), (7..8, span)]);
+    }
+
+    #[test]
+    fn markup_nests() {
+        let (text, runs) = markup("<u>a<span weight='bold'>b</span>c</u>").unwrap();
+        assert_eq!(text, "abc");
+        let underline = Style {
+            underline: true,
+            ..Style::default()
+        };
+        let both = Style {
+            bold: true,
+            ..underline.clone()
+        };
+        assert_eq!(
+            runs,
+            vec![(0..1, underline.clone()), (1..2, both), (2..3, underline)]
+        );
+    }
+
+    #[test]
+    fn malformed_markup() {
+        assert_eq!(markup("<b>unclosed"), None);
+        assert_eq!(markup("<b>a</u>"), None);
+        assert_eq!(markup("</b>"), None);
+        assert_eq!(markup("<i>a</i>"), None);
+        assert_eq!(markup("a &nbsp; b"), None);
+        assert_eq!(markup("a < b"), None);
+        assert_eq!(markup("<span size='big'>a</span>"), None);
+    }
+
+    #[test]
+    fn selected_fields() {
+        // "ab:cd:ef" styled on "b:c" and "f", showing fields 2 and 3
+        let runs = vec![(1..4, fg("red")), (7..8, fg("blue"))];
+        assert_eq!(
+            select(&runs, &[3..5, 6..8], 1),
+            vec![(0..1, fg("red")), (4..5, fg("blue"))]
+        );
+    }
+}
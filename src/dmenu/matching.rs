@@ -0,0 +1,282 @@
+use regex::RegexBuilder;
+use std::cmp::Reverse;
+
+use crate::config::{Config, MatchMode};
+use crate::drw::Drw;
+use crate::item::{Item, MatchCode};
+use crate::normalize::fold_str;
+use crate::result::*;
+
+impl MatchMode {
+    /// The mode switched to when cycling at runtime
+    pub fn next(self) -> Self {
+        match self {
+            MatchMode::Substring => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Glob,
+            MatchMode::Glob => MatchMode::Prefix,
+            MatchMode::Prefix => MatchMode::Exact,
+            MatchMode::Exact => MatchMode::Substring,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchMode::Substring => "substring",
+            MatchMode::Regex => "regex",
+            MatchMode::Glob => "glob",
+            MatchMode::Prefix => "prefix",
+            MatchMode::Exact => "exact",
+        }
+    }
+    /// Whether extending the input can only ever remove matches,
+    /// meaning the last matches can be narrowed down instead of starting over
+    pub fn narrows(self) -> bool {
+        match self {
+            MatchMode::Substring | MatchMode::Prefix => true,
+            MatchMode::Regex | MatchMode::Glob | MatchMode::Exact => false,
+        }
+    }
+    /// Regex source for the whole input, and for each part which must match on its own
+    fn patterns(self, input: &str) -> (String, Vec<String>) {
+        if input.is_empty() {
+            return (String::new(), Vec::new()); // show everything, whatever the mode
+        }
+        let whole = match self {
+            MatchMode::Substring => regex::escape(input),
+            MatchMode::Regex => input.to_owned(),
+            MatchMode::Glob => format!("^{}$", glob_to_regex(input)),
+            MatchMode::Prefix => format!("^{}", regex::escape(input)),
+            MatchMode::Exact => format!("^{}$", regex::escape(input)),
+        };
+        let parts = match self {
+            // like dmenu, every space seperated token must match
+            MatchMode::Substring => input.split_whitespace().map(regex::escape).collect(),
+            _ => vec![whole.clone()],
+        };
+        (whole, parts)
+    }
+}
+
+/// Translates `*`, `?` and `[...]` into regex, escaping everything else
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::new();
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                let negated = rest.starts_with('!') || rest.starts_with('^');
+                let start = if negated { 1 } else { 0 };
+                // a ']' right at the start of the class is part of it
+                match rest[start..]
+                    .char_indices()
+                    .skip(1)
+                    .find(|(_, c)| *c == ']')
+                {
+                    Some((end, _)) => {
+                        re.push('[');
+                        if negated {
+                            re.push('^');
+                        }
+                        for c in rest[start..start + end].chars() {
+                            if "\\[&~".contains(c) {
+                                re.push('\\'); // special inside regex classes only
+                            }
+                            re.push(c);
+                        }
+                        re.push(']');
+                        chars.nth(rest[..=start + end].chars().count() - 1);
+                    }
+                    None => re.push_str("\\["), // unclosed, so taken literally
+                }
+            }
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re
+}
+
+/// With `--smart_case`, only input with uppercase letters is case sensitive
+fn case_sensitive(config: &Config, input: &str) -> bool {
+    if config.smart_case {
+        input.chars().any(char::is_uppercase)
+    } else {
+        config.case_sensitive
+    }
+}
+
+impl Drw {
+    /// Matches items against the input according to the current `--match` mode.
+    /// Invalid patterns aren't fatal: they match nothing, and the error is shown
+    /// in place of the prompt.
+    pub fn match_by_mode(&mut self) -> CompResult<Vec<Item>> {
+        let mode = self.config.match_mode;
+        let input = if self.config.normalize {
+            fold_str(&self.input)
+        } else {
+            self.input.clone()
+        };
+        let (whole, parts) = mode.patterns(&input);
+        let case_sensitive = case_sensitive(&self.config, &input);
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+        };
+        let built = build(&whole).and_then(|re| {
+            let tokens = parts
+                .iter()
+                .map(|part| build(part))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((re, tokens))
+        });
+        let (re, tokens) = match built {
+            Ok(built) => built,
+            Err(err) => {
+                // the last line of the error is the short description
+                let err = err.to_string();
+                let reason = err.lines().last().unwrap_or_default();
+                self.pseudo_globals.match_error = Some(format!(
+                    "invalid {}: {}",
+                    mode.name(),
+                    reason.trim_start_matches("error: ")
+                ));
+                return Ok(Vec::new());
+            }
+        };
+
+        let items = self.items.as_ref().unwrap();
+        let candidates = if mode.narrows() {
+            items.candidates(&self.input)
+        } else {
+            (0..items.data.len()).collect()
+        };
+        let matched =
+            items.filter_candidates(
+                candidates,
+                self.config.parallel_threshold,
+                |item| match item.matches(&re, &tokens) {
+                    MatchCode::None => None,
+                    code => Some((code, item.highlighted(&tokens))),
+                },
+            );
+        let mut exact: Vec<(usize, Item)> = Vec::new();
+        let mut prefix: Vec<(usize, Item)> = Vec::new();
+        let mut substring: Vec<(usize, Item)> = Vec::new();
+        for (index, (code, item)) in matched {
+            match code {
+                MatchCode::Exact => exact.push((index, item)),
+                MatchCode::Prefix => prefix.push((index, item)),
+                MatchCode::Substring => substring.push((index, item)),
+                MatchCode::None => {}
+            }
+        }
+        if self.history.is_some() {
+            // the sort is stable, so equally frecent items stay in stdin order
+            for tier in [&mut exact, &mut prefix, &mut substring].iter_mut() {
+                tier.sort_by_cached_key(|(_, item)| Reverse(self.frecency(&item.output)));
+            }
+        }
+        exact.append(&mut prefix);
+        exact.append(&mut substring);
+        let (indices, matches) = exact.into_iter().unzip();
+        if mode.narrows() {
+            self.items
+                .as_mut()
+                .unwrap()
+                .cache_matches(self.input.clone(), indices);
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn glob_matches(glob: &str, text: &str) -> bool {
+        Regex::new(&format!("^{}$", glob_to_regex(glob)))
+            .unwrap()
+            .is_match(text)
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(!glob_matches("*.rs", "main.rs.bak"));
+        assert!(glob_matches("file?.txt", "file1.txt"));
+        assert!(!glob_matches("file?.txt", "file10.txt"));
+    }
+
+    #[test]
+    fn glob_escapes_regex() {
+        assert_eq!(glob_to_regex("a.b+c"), "a\\.b\\+c");
+        assert!(!glob_matches("a.b", "axb"));
+        assert!(glob_matches("(x)", "(x)"));
+    }
+
+    #[test]
+    fn glob_classes() {
+        assert!(glob_matches("[ab]*", "apple"));
+        assert!(!glob_matches("[ab]*", "cherry"));
+        assert!(glob_matches("[!ab]*", "cherry"));
+        assert!(glob_matches("[^ab]*", "cherry"));
+        assert!(glob_matches("[a-c]", "b"));
+        // a leading ']' is part of the class
+        assert!(glob_matches("[]x]", "]"));
+        // unclosed classes are taken literally
+        assert!(glob_matches("[ab", "[ab"));
+        assert!(glob_matches("[&~]", "~"));
+    }
+
+    #[test]
+    fn narrowing() {
+        assert!(MatchMode::Substring.narrows());
+        assert!(MatchMode::Prefix.narrows());
+        // typing more can match more: `a` then `a|b`, or `a` then `a*`
+        assert!(!MatchMode::Regex.narrows());
+        assert!(!MatchMode::Glob.narrows());
+        assert!(!MatchMode::Exact.narrows());
+    }
+
+    #[test]
+    fn cycling_visits_every_mode() {
+        let mut mode = MatchMode::Substring;
+        let mut names = Vec::new();
+        for _ in 0..5 {
+            names.push(mode.name());
+            mode = mode.next();
+        }
+        assert!(matches!(mode, MatchMode::Substring));
+        assert_eq!(names, ["substring", "regex", "glob", "prefix", "exact"]);
+    }
+
+    #[test]
+    fn smart_case() {
+        let mut config = Config {
+            smart_case: true,
+            ..Config::default()
+        };
+        assert!(!case_sensitive(&config, "firefox"));
+        assert!(case_sensitive(&config, "Firefox"));
+        assert!(case_sensitive(&config, "Éclair"));
+        config.smart_case = false;
+        config.case_sensitive = false;
+        assert!(!case_sensitive(&config, "Firefox"));
+        config.case_sensitive = true;
+        assert!(case_sensitive(&config, "firefox"));
+    }
+
+    #[test]
+    fn substring_tokens() {
+        let (whole, parts) = MatchMode::Substring.patterns("a.b  c");
+        assert_eq!(whole, "a\\.b  c");
+        assert_eq!(parts, ["a\\.b", "c"]);
+        let (whole, parts) = MatchMode::Exact.patterns("a b");
+        assert_eq!(whole, "^a b$");
+        assert_eq!(parts, [whole]);
+        assert_eq!(MatchMode::Regex.patterns(""), (String::new(), Vec::new()));
+    }
+}
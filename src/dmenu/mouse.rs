@@ -0,0 +1,79 @@
+use libc::c_int;
+use x11::xlib::{
+    Button1, Button2, Button3, Button4, Button5, ControlMask, ShiftMask, XButtonEvent, XMotionEvent,
+};
+
+use crate::drw::Drw;
+use crate::item::{Items, Target};
+use crate::keys::Action;
+use crate::result::*;
+
+impl Items {
+    /// What was drawn at a point in the window, if anything
+    pub fn hit(&self, x: c_int, y: c_int) -> Option<Target> {
+        self.hitboxes
+            .iter()
+            .find(|hb| x >= hb.x && x < hb.x + hb.w && y >= hb.y && y < hb.y + hb.h)
+            .map(|hb| hb.target)
+    }
+}
+
+#[allow(non_upper_case_globals)]
+impl Drw {
+    /// Clicking an item accepts it (or marks it, with the right button).
+    /// The wheel moves the selection, or pages with shift held.
+    pub fn buttonpress(&mut self, ev: XButtonEvent) -> CompResult<bool> {
+        // bool - should exit?
+        let target = self.items.as_ref().unwrap().hit(ev.x, ev.y);
+        let shift = ev.state & ShiftMask != 0;
+        match (ev.button, target) {
+            (Button1, Some(Target::Match(curr))) => {
+                self.click(curr);
+                self.act(if ev.state & ControlMask != 0 {
+                    Action::AcceptKeepOpen
+                } else {
+                    Action::Accept
+                })
+            }
+            (Button1, Some(Target::PrevPage)) => self.act(Action::PageUp),
+            (Button1, Some(Target::NextPage)) => self.act(Action::PageDown),
+            (Button2, _) => self.act(Action::Paste),
+            (Button3, Some(Target::Match(curr))) => {
+                self.click(curr);
+                self.act(Action::ToggleMark)
+            }
+            (Button4, _) => self.act(if shift {
+                Action::PageUp
+            } else {
+                Action::MoveUp
+            }),
+            (Button5, _) => self.act(if shift {
+                Action::PageDown
+            } else {
+                Action::MoveDown
+            }),
+            _ => Ok(false),
+        }
+    }
+
+    /// Selects a clicked item. The user has taken over, so don't jump to a late
+    /// `--select`. Other buttons leave that to `act`, which notices if they move.
+    fn click(&mut self, curr: usize) {
+        let items = self.items.as_mut().unwrap();
+        items.preselect = None;
+        items.curr = curr;
+    }
+
+    /// With `--hover`, the item under the pointer is selected
+    pub fn motion(&mut self, ev: XMotionEvent) -> CompResult<()> {
+        let items = self.items.as_mut().unwrap();
+        if let Some(Target::Match(curr)) = items.hit(ev.x, ev.y) {
+            if curr != items.curr {
+                items.preselect = None;
+                items.curr = curr;
+                self.draw()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::ops::Range;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Text for `--normalize`d matching: decomposed with NFKD and stripped of
+/// combining marks, so "café" and "ﬁle" can be found by typing "cafe" and "file"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Folded {
+    pub text: String,
+    origins: Vec<u32>, // for each byte of text, where its char started in the original
+}
+
+pub fn fold_str(text: &str) -> String {
+    text.nfkd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+impl Folded {
+    /// Returns None if folding changes nothing, which is most text
+    pub fn new(original: &str) -> Option<Self> {
+        let mut folded = Self {
+            text: String::new(),
+            origins: Vec::new(),
+        };
+        for (byte, c) in original.char_indices() {
+            for part in c.to_string().nfkd().filter(|c| !is_combining_mark(*c)) {
+                folded.text.push(part);
+                let len = folded.origins.len() + part.len_utf8();
+                folded.origins.resize(len, byte as u32);
+            }
+        }
+        if folded.text == original {
+            None
+        } else {
+            Some(folded)
+        }
+    }
+
+    /// Maps a byte range of the folded text back onto `original`, widening to whole chars
+    pub fn unfold(&self, original: &str, range: Range<usize>) -> Range<usize> {
+        if range.start >= range.end {
+            return 0..0;
+        }
+        let start = self.origins[range.start] as usize;
+        let last = self.origins[range.end - 1] as usize;
+        let end = last + original[last..].chars().next().map_or(0, |c| c.len_utf8());
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_accents_and_compatibility_forms() {
+        assert_eq!(fold_str("café"), "cafe");
+        assert_eq!(fold_str("ﬁle"), "file");
+        assert_eq!(fold_str("Ångström"), "Angstrom");
+        assert_eq!(fold_str("plain"), "plain");
+    }
+
+    #[test]
+    fn unchanged_text_isnt_folded() {
+        assert_eq!(Folded::new("plain ascii"), None);
+        assert_eq!(Folded::new("café").unwrap().text, "cafe");
+    }
+
+    #[test]
+    fn unfolds_to_whole_chars() {
+        let original = "ﬁlé!";
+        let folded = Folded::new(original).unwrap();
+        assert_eq!(folded.text, "file!");
+        // "i" is half of the ligature, which is highlighted whole
+        assert_eq!(folded.unfold(original, 1..2), 0..3);
+        assert_eq!(&original[folded.unfold(original, 2..4)], "lé");
+        assert_eq!(&original[folded.unfold(original, 4..5)], "!");
+        assert_eq!(folded.unfold(original, 2..2), 0..0);
+    }
+}
@@ -10,9 +10,18 @@ use overrider::*;
 #[allow(unused_imports)]
 use regex::{Regex, RegexBuilder};
 
+use crate::config::Align;
+use crate::config::Anchor;
 use crate::config::ConfigDefault;
 use crate::config::DefaultWidth;
+use crate::config::FieldSpec;
+use crate::config::GridOrder;
+use crate::config::Length;
+use crate::config::MatchMode;
+use crate::config::OutputFormat;
 use crate::config::Schemes::*;
+use crate::config::Styling;
+use crate::keys::{Key, Keymap};
 
 #[default]
 impl Drw {
@@ -63,30 +72,7 @@ impl Drw {
      * Returns - Vector of items to be drawn
      */
     pub fn gen_matches(&mut self) -> CompResult<Vec<Item>> {
-        let re = RegexBuilder::new(&regex::escape(&self.input))
-            .case_insensitive(!self.config.case_sensitive)
-            .build()
-            .map_err(|_| Die::Stderr("Could not build regex".to_owned()))?;
-        let mut exact: Vec<Item> = Vec::new();
-        let mut prefix: Vec<Item> = Vec::new();
-        let mut substring: Vec<Item> = Vec::new();
-        for item in self.get_items() {
-            match item.matches(&re) {
-                MatchCode::Exact => exact.push(item.clone()),
-                MatchCode::Prefix => prefix.push(item.clone()),
-                MatchCode::Substring => substring.push(item.clone()),
-                MatchCode::None => {}
-            }
-        }
-        exact.reserve(prefix.len() + substring.len());
-        for item in prefix {
-            // extend is broken for pointers
-            exact.push(item);
-        }
-        for item in substring {
-            exact.push(item);
-        }
-        Ok(exact)
+        self.match_by_mode()
     }
 }
 
@@ -96,9 +82,30 @@ impl ConfigDefault {
     pub fn lines() -> u32 {
         0
     }
+    pub fn columns() -> u32 {
+        1
+    }
+    pub fn grid_order() -> GridOrder {
+        GridOrder::Columns
+    }
     pub fn topbar() -> bool {
         true
     }
+    pub fn anchor() -> Option<Anchor> {
+        None
+    }
+    pub fn x_offset() -> Length {
+        Length::Pixels(0)
+    }
+    pub fn y_offset() -> Length {
+        Length::Pixels(0)
+    }
+    pub fn width() -> Option<Length> {
+        None
+    }
+    pub fn border_width() -> u32 {
+        0
+    }
     pub fn prompt() -> String {
         String::new()
     }
@@ -117,20 +124,41 @@ impl ConfigDefault {
     pub fn mon() -> i32 {
         -1
     }
-    pub fn colors() -> [[[u8; 8]; 2]; SchemeLast as usize] {
-        /*                         [  fg             bg         ]*/
-        let mut arr = [[[0; 8]; 2]; SchemeLast as usize];
-        arr[SchemeNorm as usize] = [*b"#bbbbbb\0", *b"#222222\0"];
-        arr[SchemeSel as usize] = [*b"#eeeeee\0", *b"#005577\0"];
-        arr[SchemeOut as usize] = [*b"#000000\0", *b"#00ffff\0"];
+    pub fn colors() -> [[String; 2]; SchemeLast as usize] {
+        let color = |fg: &str, bg: &str| [fg.to_owned(), bg.to_owned()];
+        /*          [  fg          bg      ]*/
+        let mut arr: [[String; 2]; SchemeLast as usize] = Default::default();
+        arr[SchemeNorm as usize] = color("#bbbbbb", "#222222");
+        arr[SchemeSel as usize] = color("#eeeeee", "#005577");
+        arr[SchemeOut as usize] = color("#000000", "#00ffff");
+        arr[SchemeHighlight as usize] = color("#ffc978", ""); // bg is only filled if set
+        arr[SchemePrompt as usize] = color("", ""); // SchemeSel unless set
+        arr[SchemeBorder as usize] = color("#005577", "#005577"); // only bg is used
+        arr[SchemeCounter as usize] = color("#bbbbbb", "#222222");
+        arr[SchemeScrollbar as usize] = color("#005577", "#222222"); // the thumb, then the track
         arr
     }
     pub fn nostdin() -> bool {
         false
     }
+    pub fn stream() -> bool {
+        false
+    }
     pub fn render_minheight() -> u32 {
         4
     }
+    pub fn hpad() -> Option<u32> {
+        None
+    }
+    pub fn vpad() -> u32 {
+        2
+    }
+    pub fn line_spacing() -> u32 {
+        0
+    }
+    pub fn align() -> Align {
+        Align::Left
+    }
     pub fn render_overrun() -> bool {
         false
     }
@@ -143,4 +171,133 @@ impl ConfigDefault {
     pub fn render_default_width() -> DefaultWidth {
         DefaultWidth::Items
     }
+    pub fn parallel_threshold() -> usize {
+        50000
+    }
+    pub fn match_mode() -> MatchMode {
+        MatchMode::Substring
+    }
+    pub fn history() -> Option<String> {
+        None
+    }
+    pub fn multi() -> bool {
+        false
+    }
+    pub fn multi_limit() -> Option<usize> {
+        None
+    }
+    pub fn delimiter() -> String {
+        "\t".to_owned()
+    }
+    pub fn with_nth() -> Option<FieldSpec> {
+        None
+    }
+    pub fn nth() -> Option<FieldSpec> {
+        None
+    }
+    pub fn output_nth() -> Option<FieldSpec> {
+        None
+    }
+    pub fn icons() -> bool {
+        false
+    }
+    pub fn icon_theme() -> String {
+        "hicolor".to_owned()
+    }
+    pub fn styling() -> Styling {
+        Styling::Plain
+    }
+    pub fn format() -> OutputFormat {
+        OutputFormat::Text
+    }
+    pub fn index_sentinel() -> String {
+        "-1".to_owned()
+    }
+    pub fn select() -> Option<String> {
+        None
+    }
+    pub fn select_index() -> Option<usize> {
+        None
+    }
+    pub fn smart_case() -> bool {
+        false
+    }
+    pub fn normalize() -> bool {
+        false
+    }
+    pub fn argb() -> bool {
+        false
+    }
+    pub fn hover() -> bool {
+        false
+    }
+    pub fn counter() -> bool {
+        false
+    }
+    pub fn scrollbar() -> bool {
+        false
+    }
+    pub fn keymap() -> Keymap {
+        use crate::keys::Action::*;
+        use x11::keysym::*;
+        use x11::xlib::{ControlMask, ShiftMask};
+        let ctrl_shift = |sym| Key::new(sym, ControlMask | ShiftMask);
+        Keymap::new(vec![
+            (Key::plain(XK_Escape), Cancel),
+            (Key::ctrl(XK_c), Cancel),
+            (Key::ctrl(XK_g), Cancel),
+            (Key::ctrl(XK_bracketleft), Cancel),
+            (Key::plain(XK_Return), Accept),
+            (Key::plain(XK_KP_Enter), Accept),
+            (Key::ctrl(XK_j), Accept),
+            (Key::ctrl(XK_m), Accept),
+            (Key::shift(XK_Return), AcceptCustom),
+            (Key::shift(XK_KP_Enter), AcceptCustom),
+            (Key::ctrl(XK_J), AcceptCustom),
+            (Key::ctrl(XK_M), AcceptCustom),
+            (Key::ctrl(XK_Return), AcceptKeepOpen),
+            (Key::ctrl(XK_KP_Enter), AcceptKeepOpen),
+            (ctrl_shift(XK_Return), AcceptCustomKeepOpen),
+            (ctrl_shift(XK_KP_Enter), AcceptCustomKeepOpen),
+            (Key::plain(XK_Tab), Complete),
+            (Key::ctrl(XK_i), Complete),
+            (Key::plain(XK_Home), First),
+            (Key::ctrl(XK_a), First),
+            (Key::alt(XK_g), First),
+            (Key::plain(XK_End), Last),
+            (Key::ctrl(XK_e), Last),
+            (Key::alt(XK_G), Last),
+            (Key::plain(XK_Prior), PageUp),
+            (Key::alt(XK_k), PageUp),
+            (Key::plain(XK_Next), PageDown),
+            (Key::alt(XK_j), PageDown),
+            (Key::plain(XK_Up), MoveUp),
+            (Key::ctrl(XK_p), MoveUp),
+            (Key::alt(XK_h), MoveUp),
+            (Key::plain(XK_Down), MoveDown),
+            (Key::ctrl(XK_n), MoveDown),
+            (Key::alt(XK_l), MoveDown),
+            (Key::plain(XK_Left), MoveLeft),
+            (Key::ctrl(XK_b), MoveLeft),
+            (Key::plain(XK_Right), MoveRight),
+            (Key::ctrl(XK_f), MoveRight),
+            (Key::ctrl(XK_Left), WordLeft),
+            (Key::alt(XK_b), WordLeft),
+            (Key::ctrl(XK_Right), WordRight),
+            (Key::alt(XK_f), WordRight),
+            (Key::plain(XK_BackSpace), DeleteBack),
+            (Key::ctrl(XK_h), DeleteBack),
+            (Key::plain(XK_Delete), DeleteForward),
+            (Key::ctrl(XK_d), DeleteForward),
+            (Key::ctrl(XK_w), DeleteWordBack),
+            (Key::ctrl(XK_BackSpace), DeleteWordBack),
+            (Key::ctrl(XK_Delete), DeleteWordForward),
+            (Key::ctrl(XK_u), DeleteToStart),
+            (Key::ctrl(XK_k), DeleteToEnd),
+            (Key::ctrl(XK_y), Paste),
+            (Key::ctrl(XK_Y), Paste),
+            (Key::ctrl(XK_t), ToggleMark),
+            (Key::ctrl(XK_r), CycleMatch),
+        ])
+    }
 }
@@ -4,13 +4,16 @@ use regex::Regex;
 use std::mem::MaybeUninit;
 use unicode_segmentation::UnicodeSegmentation;
 use x11::xlib::{
-    ControlMask, DestroyNotify, Expose, False, FocusIn, KeyPress, Mod1Mask, SelectionNotify,
-    ShiftMask, VisibilityNotify, VisibilityUnobscured, XEvent, XFilterEvent, XInternAtom,
-    XKeyEvent, XLookupBoth, XLookupChars, XLookupKeySym, XNextEvent, XRaiseWindow, XmbLookupString,
+    ButtonPress, ControlMask, DestroyNotify, Expose, False, FocusIn, KeyPress, Mod1Mask,
+    MotionNotify, SelectionNotify, VisibilityNotify, VisibilityUnobscured, XEvent, XFilterEvent,
+    XInternAtom, XKeyEvent, XLookupBoth, XLookupChars, XLookupKeySym, XNextEvent, XPending,
+    XRaiseWindow, XmbLookupString,
 };
 
+use crate::config::{GridOrder, OutputFormat};
 use crate::drw::Drw;
 use crate::item::Partition;
+use crate::keys::Action;
 use crate::result::*;
 use crate::util::grabfocus;
 
@@ -20,7 +23,14 @@ impl Drw {
         unsafe {
             let utf8 = XInternAtom(self.dpy, "UTF8_STRING\0".as_ptr() as *mut c_char, False);
             let mut ev: XEvent = MaybeUninit::uninit().assume_init();
-            while XNextEvent(self.dpy, &mut ev) == 0 {
+            loop {
+                if self.stream.is_some() && XPending(self.dpy) == 0 {
+                    self.await_stdin()?;
+                    continue;
+                }
+                if XNextEvent(self.dpy, &mut ev) != 0 {
+                    break;
+                }
                 if XFilterEvent(&mut ev, self.pseudo_globals.win) != 0 {
                     continue;
                 }
@@ -45,6 +55,12 @@ impl Drw {
                         Ok(false) => {}
                         Err(err) => return Err(err),
                     },
+                    ButtonPress => match self.buttonpress(ev.button) {
+                        Ok(true) => break,
+                        Ok(false) => {}
+                        Err(err) => return Err(err),
+                    },
+                    MotionNotify => self.motion(ev.motion)?,
                     SelectionNotify => {
                         if ev.selection.property == utf8 {
                             self.paste()?;
@@ -64,7 +80,6 @@ impl Drw {
 
     fn keypress(&mut self, mut ev: XKeyEvent) -> CompResult<bool> {
         // bool - should exit?
-        use x11::keysym::*;
         unsafe {
             let buf: [u8; 32] = [0; 32];
             let mut __ksym = MaybeUninit::<c_ulong>::uninit();
@@ -77,297 +92,122 @@ impl Drw {
                 __ksym.as_mut_ptr(),
                 status.as_mut_ptr(),
             );
-            let mut ksym = __ksym.assume_init() as u32;
             match status.assume_init() {
-                XLookupChars => {
-                    if iscntrl(*(buf.as_ptr() as *mut i32)) == 0 {
-                        self.keyprocess(ksym, buf, len, ev.state)?;
-                    }
-                }
+                XLookupChars => return self.insert(buf, len),
                 XLookupKeySym | XLookupBoth => {}
                 _ => return Ok(false), /* XLookupNone, XBufferOverflow */
             }
-            const control: bool = true;
-            const mod1: bool = false;
+            let ksym = __ksym.assume_init() as u32;
+            if let Some(action) = self.config.keymap.lookup(ksym, ev.state) {
+                return self.act(action);
+            }
             if (ev.state & ControlMask) != 0 || (ev.state & Mod1Mask) != 0 {
-                match (ksym, (ev.state & ControlMask) != 0) {
-                    (XK_a, control) | (XK_g, mod1) => ksym = XK_Home,
-                    (XK_b, control) => ksym = XK_Left,
-                    (XK_c, control) => ksym = XK_Escape,
-                    (XK_d, control) => ksym = XK_Delete,
-                    (XK_e, control) | (XK_G, mod1) => ksym = XK_End,
-                    (XK_f, control) => ksym = XK_Right,
-                    (XK_g, control) | (XK_bracketleft, control) => ksym = XK_Escape,
-                    (XK_h, control) => ksym = XK_BackSpace,
-                    (XK_i, control) => ksym = XK_Tab,
-                    (XK_j, mod1) => ksym = XK_Next,
-                    (XK_k, mod1) => ksym = XK_Prior,
-                    (XK_n, control) | (XK_l, mod1) => ksym = XK_Down,
-                    (XK_p, control) | (XK_h, mod1) => ksym = XK_Up,
-                    (XK_j, control) | (XK_J, control) | (XK_m, control) | (XK_M, control) => {
-                        ksym = XK_Return;
-                        ev.state &= !ControlMask;
-                    }
-                    (XK_k, control) => {
-                        // delete all to the left
-                        self.input = self
-                            .input
-                            .graphemes(true)
-                            .take(self.pseudo_globals.cursor)
-                            .collect::<String>();
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_u, control) => {
-                        // delete all to the right
-                        self.input = self
-                            .input
-                            .graphemes(true)
-                            .skip(self.pseudo_globals.cursor)
-                            .collect::<String>();
-                        self.pseudo_globals.cursor = 0;
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_w, control) | (XK_BackSpace, control) => {
-                        // Delete word to the left
-                        let mut state = 0;
-                        let mut found = 0;
-                        self.input = self
-                            .input
-                            .grapheme_indices(true)
-                            .rev()
-                            .filter_map(|(i, c)| {
-                                if state == 0 && i < self.pseudo_globals.cursor {
-                                    state = 1; // searching for cursor
-                                }
-                                if state == 1 && c != " " {
-                                    state = 2; // looking for previous word
-                                }
-                                if state == 2 && c == " " {
-                                    state = 3; // skipping past next word
-                                }
-                                if state == 0 || state == 4 {
-                                    Some(c)
-                                } else if state == 3 {
-                                    found = i + 1;
-                                    state = 4;
-                                    Some(c)
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<&str>>()
-                            .into_iter()
-                            .rev()
-                            .collect::<String>();
-                        self.pseudo_globals.cursor = found;
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_Delete, control) => {
-                        // Delete word to the right
-                        let mut state = 0;
-                        self.input = self
-                            .input
-                            .grapheme_indices(true)
-                            .filter_map(|(i, c)| {
-                                if state == 0 && i >= self.pseudo_globals.cursor {
-                                    state = 1; // searching for cursor
-                                }
-                                if state == 1 && c != " " {
-                                    state = 2; // looking for next word
-                                }
-                                if state == 2 && c == " " {
-                                    state = 3; // skipping past next word
-                                }
-                                if state == 0 || state == 4 {
-                                    Some(c)
-                                } else if state == 3 {
-                                    state = 4;
-                                    Some(c)
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<String>();
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_y, control) | (XK_Y, control) => {
-                        // paste selection
-                        return self.paste().map(|_| false);
-                    }
-                    (XK_Left, control) | (XK_b, mod1) => {
-                        // skip to word boundary on left
-                        self.pseudo_globals.cursor = self
-                            .input
-                            .grapheme_indices(true)
-                            .rev()
-                            .skip(self.input.graphemes(true).count() - self.pseudo_globals.cursor)
-                            .skip_while(|(_, c)| *c == " ") // find last word
-                            .skip_while(|(_, c)| *c != " ") // skip past it
-                            .next()
-                            .map(|(i, _)| i + 1)
-                            .unwrap_or(0);
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_Right, control) | (XK_f, mod1) => {
-                        // skip to word boundary on right
-                        self.pseudo_globals.cursor = self
-                            .input
-                            .grapheme_indices(true)
-                            .skip(self.pseudo_globals.cursor + 1)
-                            .skip_while(|(_, c)| *c == " ") // find next word
-                            .skip_while(|(_, c)| *c != " ") // skip past it
-                            .next()
-                            .map(|(i, _)| i)
-                            .unwrap_or(self.input.graphemes(true).count());
-                        return self.draw().map(|_| false);
-                    }
-                    (XK_Return, control) | (XK_KP_Enter, control) => {} // pass through
-                    _ => return Ok(false),
-                }
+                return Ok(false); // unbound shortcut
             }
-            self.keyprocess(ksym, buf, len, ev.state)
+            self.insert(buf, len)
+        }
+    }
+
+    /// What `dispose` is given for a selection, according to `--format`.
+    /// Custom input has no index, so gets `--index_sentinel` instead.
+    fn formatted(&self, index: Option<usize>, output: String) -> String {
+        let index = index.map_or_else(|| self.config.index_sentinel.clone(), |i| i.to_string());
+        match self.config.format {
+            OutputFormat::Text => output,
+            OutputFormat::Index => index,
+            OutputFormat::Both => format!("{}{}{}", index, self.config.delimiter, output),
         }
     }
 
-    fn keyprocess(&mut self, ksym: u32, buf: [u8; 32], len: i32, state: u32) -> CompResult<bool> {
+    /// Runs a bound action, see `keys.rs` for the default bindings
+    pub fn act(&mut self, action: Action) -> CompResult<bool> {
         // bool - should exit
-        use x11::keysym::*;
-        unsafe {
-            match ksym {
-                XK_Escape => return Die::stderr("".to_owned()), // exit with error code 1
-                XK_Return | XK_KP_Enter => {
-                    return if (state & ShiftMask) == 0
-                        && self.items.as_mut().unwrap().cached_partitions.len() > 0
-                    {
-                        let (partition_i, partition) = Partition::decompose(
-                            &self.items.as_ref().unwrap().cached_partitions,
-                            self,
-                        ); // find the current selection
-                           // and print
-                        self.dispose(
-                            self.items.as_ref().unwrap().cached_partitions[partition][partition_i]
-                                .text
-                                .clone(),
-                            (state & ControlMask) == 0,
-                        )
-                    } else {
-                        // if Shift-Enter (or no valid options), print contents exactly as in input and return, ignoring selection
-                        self.dispose(self.input.clone(), (state & ControlMask) == 0)
-                    };
-                }
-                XK_Tab => {
-                    if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
-                        // find the current selection
-                        let (partition_i, partition) = Partition::decompose(
-                            &self.items.as_ref().unwrap().cached_partitions,
-                            self,
-                        ); // and autocomplete
-                        self.input = self.items.as_mut().unwrap().cached_partitions[partition]
-                            [partition_i]
-                            .text
-                            .clone();
-                        self.pseudo_globals.cursor = self.input.graphemes(true).count();
-                        self.items.as_mut().unwrap().curr = 0;
-                    } else {
-                        return Ok(false);
-                    }
-                }
-                XK_Home => {
-                    if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
-                        self.items.as_mut().unwrap().curr = 0;
-                    } else {
-                        return Ok(false);
-                    }
+        let (curr, input) = (self.items.as_ref().unwrap().curr, self.input.clone());
+        match action {
+            Action::Cancel => return Die::stderr("".to_owned()), // exit with error code 1
+            Action::Accept => return self.accept(false, false),
+            Action::AcceptCustom => return self.accept(true, false),
+            Action::AcceptKeepOpen => return self.accept(false, true),
+            Action::AcceptCustomKeepOpen => return self.accept(true, true),
+            Action::Complete => {
+                if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
+                    // find the current selection
+                    let (partition_i, partition) =
+                        Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self); // and autocomplete
+                                                                                                     // the text the user sees, not what --nth searches or --output_nth prints
+                    self.input = self.items.as_mut().unwrap().cached_partitions[partition]
+                        [partition_i]
+                        .display
+                        .clone();
+                    self.pseudo_globals.cursor = self.input.graphemes(true).count();
+                    self.items.as_mut().unwrap().curr = 0;
+                } else {
+                    return Ok(false);
                 }
-                XK_End => {
-                    if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
-                        self.items.as_mut().unwrap().curr = self
-                            .items
-                            .as_mut()
-                            .unwrap()
-                            .cached_partitions
-                            .iter()
-                            .fold(0, |acc, cur| acc + cur.len())
-                            - 1;
-                    } else {
-                        return Ok(false);
-                    }
+            }
+            Action::First => {
+                if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
+                    self.items.as_mut().unwrap().curr = 0;
+                } else {
+                    return Ok(false);
                 }
-                XK_Next => {
-                    // PgDn
-                    let (partition_i, partition) =
-                        Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
-                    if partition + 1 < self.items.as_mut().unwrap().cached_partitions.len() {
-                        self.items.as_mut().unwrap().curr +=
-                            self.items.as_mut().unwrap().cached_partitions[partition].len()
-                                - partition_i;
-                    } else {
-                        return Ok(false);
-                    }
+            }
+            Action::Last => {
+                if self.items.as_mut().unwrap().cached_partitions.len() > 0 {
+                    self.items.as_mut().unwrap().curr = self
+                        .items
+                        .as_mut()
+                        .unwrap()
+                        .cached_partitions
+                        .iter()
+                        .fold(0, |acc, cur| acc + cur.len())
+                        - 1;
+                } else {
+                    return Ok(false);
                 }
-                XK_Prior => {
-                    // PgUp
-                    let (partition_i, partition) =
-                        Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
-                    if partition > 0 {
-                        self.items.as_mut().unwrap().curr -=
-                            self.items.as_mut().unwrap().cached_partitions[partition - 1].len()
-                                + partition_i;
-                    } else {
-                        return Ok(false);
-                    }
+            }
+            Action::PageDown => {
+                let (partition_i, partition) =
+                    Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
+                if partition + 1 < self.items.as_mut().unwrap().cached_partitions.len() {
+                    self.items.as_mut().unwrap().curr +=
+                        self.items.as_mut().unwrap().cached_partitions[partition].len()
+                            - partition_i;
+                } else {
+                    return Ok(false);
                 }
-                XK_Left => {
-                    if self.config.lines == 0
-                        && self.pseudo_globals.cursor == self.input.graphemes(true).count()
-                        && self.items.as_mut().unwrap().curr > 0
-                    {
-                        self.items.as_mut().unwrap().curr -= 1; // move selection
-                    } else {
-                        // move cursor
-                        if self.pseudo_globals.cursor > 0 {
-                            self.pseudo_globals.cursor -= 1;
-                        } else {
-                            return Ok(false);
-                        }
-                    }
+            }
+            Action::PageUp => {
+                let (partition_i, partition) =
+                    Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
+                if partition > 0 {
+                    self.items.as_mut().unwrap().curr -=
+                        self.items.as_mut().unwrap().cached_partitions[partition - 1].len()
+                            + partition_i;
+                } else {
+                    return Ok(false);
                 }
-                XK_Right => {
-                    if self.config.lines == 0
-                        && self.pseudo_globals.cursor == self.input.graphemes(true).count()
-                    {
-                        // move selection
-                        if self.items.as_mut().unwrap().curr + 1
-                            < self
-                                .items
-                                .as_mut()
-                                .unwrap()
-                                .cached_partitions
-                                .iter()
-                                .fold(0, |acc, cur| acc + cur.len())
-                        {
-                            self.items.as_mut().unwrap().curr += 1;
-                        } else {
-                            return Ok(false);
-                        }
-                    } else {
-                        // move cursor
-                        if self.pseudo_globals.cursor < self.input.len() {
-                            self.pseudo_globals.cursor += 1;
-                        } else {
-                            return Ok(false);
-                        }
-                    }
+            }
+            Action::MoveLeft => match self.sideways_step() {
+                Some(step)
+                    if self.pseudo_globals.cursor == self.input.graphemes(true).count()
+                        && self.items.as_ref().unwrap().curr >= step =>
+                {
+                    self.items.as_mut().unwrap().curr -= step; // move selection
                 }
-                XK_Up => {
-                    if self.items.as_mut().unwrap().curr > 0 {
-                        self.items.as_mut().unwrap().curr -= 1;
+                _ => {
+                    // move cursor
+                    if self.pseudo_globals.cursor > 0 {
+                        self.pseudo_globals.cursor -= 1;
                     } else {
                         return Ok(false);
                     }
                 }
-                XK_Down => {
-                    if self.items.as_mut().unwrap().curr + 1
+            },
+            Action::MoveRight => match self.sideways_step() {
+                Some(step) if self.pseudo_globals.cursor == self.input.graphemes(true).count() => {
+                    // move selection
+                    if self.items.as_mut().unwrap().curr + step
                         < self
                             .items
                             .as_mut()
@@ -376,58 +216,296 @@ impl Drw {
                             .iter()
                             .fold(0, |acc, cur| acc + cur.len())
                     {
-                        self.items.as_mut().unwrap().curr += 1;
+                        self.items.as_mut().unwrap().curr += step;
                     } else {
                         return Ok(false);
                     }
                 }
-                XK_BackSpace => {
-                    if self.pseudo_globals.cursor > 0 {
-                        let tmp: String = self.input.drain(..).collect();
-                        let mut iter = tmp.graphemes(true);
-                        self.input = (&mut iter)
-                            .take(self.pseudo_globals.cursor - 1)
-                            .collect::<String>();
-                        iter.next(); // get rid of one char
-                        self.input.push_str(&iter.collect::<String>());
-                        self.pseudo_globals.cursor -= 1;
-                    } else {
-                        return Ok(false);
-                    }
-                }
-                XK_Delete => {
+                _ => {
+                    // move cursor
                     if self.pseudo_globals.cursor < self.input.len() {
-                        let tmp: String = self.input.drain(..).collect();
-                        let mut iter = tmp.graphemes(true);
-                        self.input = (&mut iter)
-                            .take(self.pseudo_globals.cursor)
-                            .collect::<String>();
-                        iter.next(); // get rid of one char
-                        self.input.push_str(&iter.collect::<String>());
+                        self.pseudo_globals.cursor += 1;
                     } else {
                         return Ok(false);
                     }
                 }
-                _ => {
-                    // all others, assumed to be normal chars
-                    if iscntrl(*(buf.as_ptr() as *mut i32)) == 0 {
-                        let tmp: String = self.input.drain(..).collect();
-                        let mut iter = tmp.graphemes(true);
-                        self.input = (&mut iter).take(self.pseudo_globals.cursor).collect();
-                        self.pseudo_globals.cursor += buf[..len as usize]
-                            .iter()
-                            .fold(0, |acc, c| acc + if *c > 0 { 1 } else { 0 });
-                        self.input
-                            .push_str(&String::from_utf8_lossy(&buf[..len as usize]));
-                        self.input.push_str(&iter.collect::<String>());
-                        self.items.as_mut().unwrap().curr = 0;
-                    } else {
-                        return Ok(false);
+            },
+            Action::MoveUp => {
+                let step = self.vertical_step();
+                if self.items.as_mut().unwrap().curr >= step {
+                    self.items.as_mut().unwrap().curr -= step;
+                } else {
+                    return Ok(false);
+                }
+            }
+            Action::MoveDown => {
+                let step = self.vertical_step();
+                if self.items.as_mut().unwrap().curr + step
+                    < self
+                        .items
+                        .as_mut()
+                        .unwrap()
+                        .cached_partitions
+                        .iter()
+                        .fold(0, |acc, cur| acc + cur.len())
+                {
+                    self.items.as_mut().unwrap().curr += step;
+                } else {
+                    return Ok(false);
+                }
+            }
+            Action::WordLeft => {
+                // skip to word boundary on left
+                self.pseudo_globals.cursor = self
+                    .input
+                    .grapheme_indices(true)
+                    .rev()
+                    .skip(self.input.graphemes(true).count() - self.pseudo_globals.cursor)
+                    .skip_while(|(_, c)| *c == " ") // find last word
+                    .skip_while(|(_, c)| *c != " ") // skip past it
+                    .next()
+                    .map(|(i, _)| i + 1)
+                    .unwrap_or(0);
+            }
+            Action::WordRight => {
+                // skip to word boundary on right
+                self.pseudo_globals.cursor = self
+                    .input
+                    .grapheme_indices(true)
+                    .skip(self.pseudo_globals.cursor + 1)
+                    .skip_while(|(_, c)| *c == " ") // find next word
+                    .skip_while(|(_, c)| *c != " ") // skip past it
+                    .next()
+                    .map(|(i, _)| i)
+                    .unwrap_or(self.input.graphemes(true).count());
+            }
+            Action::DeleteBack => {
+                if self.pseudo_globals.cursor > 0 {
+                    let tmp: String = self.input.drain(..).collect();
+                    let mut iter = tmp.graphemes(true);
+                    self.input = (&mut iter)
+                        .take(self.pseudo_globals.cursor - 1)
+                        .collect::<String>();
+                    iter.next(); // get rid of one char
+                    self.input.push_str(&iter.collect::<String>());
+                    self.pseudo_globals.cursor -= 1;
+                } else {
+                    return Ok(false);
+                }
+            }
+            Action::DeleteForward => {
+                if self.pseudo_globals.cursor < self.input.len() {
+                    let tmp: String = self.input.drain(..).collect();
+                    let mut iter = tmp.graphemes(true);
+                    self.input = (&mut iter)
+                        .take(self.pseudo_globals.cursor)
+                        .collect::<String>();
+                    iter.next(); // get rid of one char
+                    self.input.push_str(&iter.collect::<String>());
+                } else {
+                    return Ok(false);
+                }
+            }
+            Action::DeleteWordBack => {
+                let mut state = 0;
+                let mut found = 0;
+                self.input = self
+                    .input
+                    .grapheme_indices(true)
+                    .rev()
+                    .filter_map(|(i, c)| {
+                        if state == 0 && i < self.pseudo_globals.cursor {
+                            state = 1; // searching for cursor
+                        }
+                        if state == 1 && c != " " {
+                            state = 2; // looking for previous word
+                        }
+                        if state == 2 && c == " " {
+                            state = 3; // skipping past next word
+                        }
+                        if state == 0 || state == 4 {
+                            Some(c)
+                        } else if state == 3 {
+                            found = i + 1;
+                            state = 4;
+                            Some(c)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<&str>>()
+                    .into_iter()
+                    .rev()
+                    .collect::<String>();
+                self.pseudo_globals.cursor = found;
+            }
+            Action::DeleteWordForward => {
+                let mut state = 0;
+                self.input = self
+                    .input
+                    .grapheme_indices(true)
+                    .filter_map(|(i, c)| {
+                        if state == 0 && i >= self.pseudo_globals.cursor {
+                            state = 1; // searching for cursor
+                        }
+                        if state == 1 && c != " " {
+                            state = 2; // looking for next word
+                        }
+                        if state == 2 && c == " " {
+                            state = 3; // skipping past next word
+                        }
+                        if state == 0 || state == 4 {
+                            Some(c)
+                        } else if state == 3 {
+                            state = 4;
+                            Some(c)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<String>();
+            }
+            Action::DeleteToStart => {
+                self.input = self
+                    .input
+                    .graphemes(true)
+                    .skip(self.pseudo_globals.cursor)
+                    .collect::<String>();
+                self.pseudo_globals.cursor = 0;
+            }
+            Action::DeleteToEnd => {
+                self.input = self
+                    .input
+                    .graphemes(true)
+                    .take(self.pseudo_globals.cursor)
+                    .collect::<String>();
+            }
+            Action::Paste => return self.paste().map(|_| false),
+            Action::ToggleMark => {
+                // mark or unmark the selection, for --multi
+                if self.config.multi && !self.items.as_ref().unwrap().cached_partitions.is_empty() {
+                    let (partition_i, partition) =
+                        Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self);
+                    let selected = self.items.as_ref().unwrap().cached_partitions[partition]
+                        [partition_i]
+                        .index;
+                    if let Some(index) = selected {
+                        let marked = self.get_items().iter().filter(|item| item.out).count();
+                        let allowed = self.get_items()[index].out
+                            || !matches!(self.config.multi_limit, Some(limit) if marked >= limit);
+                        if allowed {
+                            let item = &mut self.get_items_mut()[index];
+                            item.out = !item.out;
+                        }
                     }
                 }
             }
-            self.draw()?;
+            Action::CycleMatch => {
+                // the last matches don't narrow down the new ones
+                self.config.match_mode = self.config.match_mode.next();
+                self.items.as_mut().unwrap().match_cache = None;
+                self.items.as_mut().unwrap().curr = 0;
+            }
+        }
+        let items = self.items.as_mut().unwrap();
+        if items.curr != curr || self.input != input {
+            // the user has taken over, so --select stops following its item
+            items.preselect = None;
+        }
+        self.draw()?;
+        Ok(false)
+    }
+
+    /// How far left and right move the selection, if they do rather than move the cursor.
+    /// In a `--columns` grid filled by column, that's a whole column.
+    fn sideways_step(&self) -> Option<usize> {
+        if self.config.lines == 0 {
+            Some(1)
+        } else if self.config.columns <= 1 {
+            None
+        } else {
+            match self.config.grid_order {
+                GridOrder::Columns => Some(self.config.lines as usize),
+                GridOrder::Rows => Some(1),
+            }
+        }
+    }
+
+    /// How far up and down move the selection
+    fn vertical_step(&self) -> usize {
+        if self.config.lines > 0 && self.config.columns > 1 {
+            match self.config.grid_order {
+                GridOrder::Columns => 1,
+                GridOrder::Rows => self.config.columns as usize,
+            }
+        } else {
+            1
+        }
+    }
+
+    /// Outputs the selection, or the input itself if `custom` or nothing matches
+    fn accept(&mut self, custom: bool, keep_open: bool) -> CompResult<bool> {
+        let marked = if !custom {
+            self.items.as_ref().unwrap().marked()
+        } else {
+            Vec::new()
+        };
+        if !marked.is_empty() {
+            // with --multi, print every marked item instead
+            for (_, output) in &marked {
+                self.record_history(output);
+            }
+            self.save_history();
+            let mut exit = false;
+            for (index, output) in marked {
+                let output = self.formatted(index, output);
+                exit |= self.dispose(output, !keep_open)?;
+            }
+            if !exit {
+                // staying open, so start a fresh selection
+                for item in self.get_items_mut() {
+                    item.out = false;
+                }
+                self.draw()?;
+            }
+            return Ok(exit);
+        }
+        let (index, output) = if !custom && self.items.as_mut().unwrap().cached_partitions.len() > 0
+        {
+            let (partition_i, partition) =
+                Partition::decompose(&self.items.as_ref().unwrap().cached_partitions, self); // find the current selection
+            let item = &self.items.as_ref().unwrap().cached_partitions[partition][partition_i];
+            (item.index, item.output.clone())
+        } else {
+            // if Shift-Enter (or no valid options), print contents exactly as in input and return, ignoring selection
+            (None, self.input.clone())
+        };
+        // and print
+        self.record_history(&output);
+        self.save_history();
+        let output = self.formatted(index, output);
+        self.dispose(output, !keep_open)
+    }
+
+    /// Types text at the cursor
+    fn insert(&mut self, buf: [u8; 32], len: i32) -> CompResult<bool> {
+        unsafe {
+            if iscntrl(*(buf.as_ptr() as *mut i32)) != 0 {
+                return Ok(false);
+            }
         }
+        let tmp: String = self.input.drain(..).collect();
+        let mut iter = tmp.graphemes(true);
+        self.input = (&mut iter).take(self.pseudo_globals.cursor).collect();
+        self.pseudo_globals.cursor += buf[..len as usize]
+            .iter()
+            .fold(0, |acc, c| acc + if *c > 0 { 1 } else { 0 });
+        self.input
+            .push_str(&String::from_utf8_lossy(&buf[..len as usize]));
+        self.input.push_str(&iter.collect::<String>());
+        self.items.as_mut().unwrap().curr = 0;
+        self.items.as_mut().unwrap().preselect = None;
+        self.draw()?;
         Ok(false)
     }
 
@@ -458,6 +536,7 @@ impl Drw {
                 self.input.push_str(&clip);
                 self.input.push_str(&iter.collect::<String>());
                 self.pseudo_globals.cursor += clip.len();
+                self.items.as_mut().unwrap().preselect = None;
                 self.draw()
             }
             Err(err) => return Die::stderr(err.to_string()),
@@ -3,15 +3,15 @@ use std::mem::MaybeUninit;
 use std::ptr;
 use x11::xinerama::{XineramaQueryScreens, XineramaScreenInfo};
 use x11::xlib::{
-    CWBackPixel, CWEventMask, CWOverrideRedirect, ExposureMask, FocusChangeMask, KeyPressMask,
-    PointerRoot, SubstructureNotifyMask, VisibilityChangeMask, Window, XClassHint, XCreateIC,
-    XCreateWindow, XFree, XGetInputFocus, XGetWindowAttributes, XIMPreeditNothing,
-    XIMStatusNothing, XMapRaised, XOpenIM, XQueryPointer, XQueryTree, XSelectInput, XSetClassHint,
-    XSetWindowAttributes,
+    ButtonPressMask, CWBackPixel, CWBorderPixel, CWColormap, CWEventMask, CWOverrideRedirect,
+    ExposureMask, FocusChangeMask, KeyPressMask, PointerMotionMask, PointerRoot,
+    SubstructureNotifyMask, VisibilityChangeMask, Window, XClassHint, XCreateIC, XCreateWindow,
+    XFree, XGetInputFocus, XGetWindowAttributes, XIMPreeditNothing, XIMStatusNothing, XMapRaised,
+    XOpenIM, XQueryPointer, XQueryTree, XSelectInput, XSetClassHint, XSetWindowAttributes,
 };
 
 use crate::additional_bindings::xlib::{XNClientWindow, XNFocusWindow, XNInputStyle};
-use crate::config::{Clrs::*, Schemes::*};
+use crate::config::{Anchor, Clrs::*, Edge::*, Length, Schemes::*};
 use crate::drw::Drw;
 use crate::result::*;
 use crate::util::grabfocus;
@@ -24,7 +24,46 @@ fn intersect(x: c_int, y: c_int, w: c_int, h: c_int, r: *mut XineramaScreenInfo)
     }
 }
 
+impl Length {
+    fn resolve(self, total: c_int) -> c_int {
+        match self {
+            Length::Pixels(px) => px,
+            Length::Percent(percent) => (total as f32 * percent / 100.0) as c_int,
+        }
+    }
+}
+
 impl Drw {
+    /// Sets the menu width for a screen of the given size, and returns where
+    /// on that screen the menu goes, according to `--anchor` and the offsets.
+    /// X draws the border outside the window, so it's taken off the width.
+    fn place(&mut self, sw: c_int, sh: c_int) -> (c_int, c_int) {
+        let border = 2 * self.config.border_width as c_int;
+        self.w = (self.config.width.map_or(sw, |w| w.resolve(sw).min(sw)) - border).max(1);
+        let Anchor(horizontal, vertical) = self.config.anchor.unwrap_or(if self.config.topbar {
+            Anchor(Start, Start)
+        } else {
+            Anchor(Start, End)
+        });
+        let along = |edge, space: c_int, offset: c_int| match edge {
+            Start => offset,
+            Middle => space / 2 + offset,
+            End => space - offset,
+        };
+        (
+            along(
+                horizontal,
+                sw - self.w - border,
+                self.config.x_offset.resolve(sw),
+            ),
+            along(
+                vertical,
+                sh - self.h - border,
+                self.config.y_offset.resolve(sh),
+            ),
+        )
+    }
+
     pub fn setup(&mut self, parentwin: u64, root: u64) -> CompResult<()> {
         unsafe {
             let mut x: c_int;
@@ -37,9 +76,9 @@ impl Drw {
 
             // appearances are set up in constructor
 
-            self.pseudo_globals.bh = (self.fonts.iter().map(|f| f.height).max().unwrap() + 4)
-                .max(self.config.render_minheight);
-            self.h = ((self.config.lines + 1) * self.pseudo_globals.bh) as c_int;
+            self.h = (self.pseudo_globals.bh
+                + self.config.lines * (self.pseudo_globals.bh + self.config.line_spacing))
+                as c_int;
 
             let mut dws: *mut Window = ptr::null_mut();
             let mut w = MaybeUninit::<Window>::uninit();
@@ -132,14 +171,10 @@ impl Drw {
                         }
                     }
                 }
-                x = (*info.offset(i as isize)).x_org as c_int;
-                y = (*info.offset(i as isize)).y_org as c_int
-                    + (if self.config.topbar {
-                        0
-                    } else {
-                        (*info.offset(i as isize)).height as c_int - self.h as c_int
-                    });
-                self.w = (*info.offset(i as isize)).width as c_int;
+                let screen = *info.offset(i as isize);
+                let (sx, sy) = self.place(screen.width as c_int, screen.height as c_int);
+                x = screen.x_org as c_int + sx;
+                y = screen.y_org as c_int + sy;
                 XFree(info as *mut c_void);
             } else {
                 if XGetWindowAttributes(self.dpy, parentwin, &mut self.wa) == 0 {
@@ -148,13 +183,9 @@ impl Drw {
                         parentwin
                     ));
                 }
-                x = 0;
-                y = if self.config.topbar {
-                    0
-                } else {
-                    self.wa.height - self.h as c_int
-                };
-                self.w = self.wa.width;
+                let (px, py) = self.place(self.wa.width, self.wa.height);
+                x = px;
+                y = py;
             }
 
             let mut swa = XSetWindowAttributes {
@@ -162,15 +193,25 @@ impl Drw {
                 background_pixel: (*self.pseudo_globals.schemeset[SchemeNorm as usize]
                     [ColBg as usize])
                     .pixel,
-                event_mask: ExposureMask | KeyPressMask | VisibilityChangeMask,
+                event_mask: ExposureMask
+                    | KeyPressMask
+                    | VisibilityChangeMask
+                    | ButtonPressMask
+                    | if self.config.hover {
+                        PointerMotionMask
+                    } else {
+                        0
+                    },
                 background_pixmap: 0,
                 backing_pixel: 0,
                 backing_store: 0,
                 backing_planes: 0,
                 bit_gravity: 0,
-                border_pixel: 0,
+                border_pixel: (*self.pseudo_globals.schemeset[SchemeBorder as usize]
+                    [ColBg as usize])
+                    .pixel,
                 border_pixmap: 0,
-                colormap: 0,
+                colormap: self.cmap,
                 cursor: 0,
                 do_not_propagate_mask: false as c_long,
                 save_under: 0,
@@ -183,11 +224,11 @@ impl Drw {
                 y,
                 self.w as u32,
                 self.h as u32,
+                self.config.border_width,
+                self.depth,
                 0,
-                0,
-                0,
-                ptr::null_mut(),
-                CWOverrideRedirect | CWBackPixel | CWEventMask,
+                self.visual,
+                CWOverrideRedirect | CWBackPixel | CWBorderPixel | CWColormap | CWEventMask,
                 &mut swa,
             );
             XSetClassHint(self.dpy, self.pseudo_globals.win, &mut ch);
@@ -0,0 +1,108 @@
+use libc::{c_int, c_void, close, pipe, poll, pollfd, read, write, POLLHUP, POLLIN};
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use x11::xlib::XConnectionNumber;
+
+use crate::drw::Drw;
+use crate::result::*;
+use crate::util::format_lines;
+
+/// Reads stdin on its own thread, so the menu can be shown before stdin is closed
+#[derive(Debug)]
+pub struct StdinStream {
+    lines: Receiver<io::Result<String>>,
+    pending: Arc<AtomicBool>, // set when the reader has woken us up and we haven't looked yet
+    wake: c_int,              // read end of a pipe, readable once lines have arrived
+}
+
+impl StdinStream {
+    pub fn spawn() -> CompResult<Self> {
+        let mut fds: [c_int; 2] = [0; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return Die::stderr("--stream: could not create pipe".to_owned());
+        }
+        let (sender, lines) = mpsc::channel();
+        let pending = Arc::new(AtomicBool::new(false));
+        let notify = fds[1];
+        let reader_pending = pending.clone();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let failed = line.is_err();
+                if sender.send(line).is_err() {
+                    break;
+                }
+                // only one wakeup per batch, otherwise the pipe fills up
+                if !reader_pending.swap(true, Ordering::AcqRel) {
+                    unsafe { write(notify, b"\0".as_ptr() as *const c_void, 1) };
+                }
+                if failed {
+                    break;
+                }
+            }
+            drop(sender);
+            unsafe { close(notify) }; // hangs up the pipe, so the main thread knows we're done
+        });
+        Ok(Self {
+            lines,
+            pending,
+            wake: fds[0],
+        })
+    }
+}
+
+impl Drop for StdinStream {
+    fn drop(&mut self) {
+        unsafe { close(self.wake) };
+    }
+}
+
+impl Drw {
+    /// Blocks until either an X event or more of stdin is available.
+    /// New lines are added to the items, and the menu is redrawn.
+    pub fn await_stdin(&mut self) -> CompResult<()> {
+        let stream = self.stream.as_ref().unwrap();
+        let mut fds = [
+            pollfd {
+                fd: unsafe { XConnectionNumber(self.dpy) },
+                events: POLLIN,
+                revents: 0,
+            },
+            pollfd {
+                fd: stream.wake,
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+        if unsafe { poll(fds.as_mut_ptr(), fds.len() as _, -1) } < 0 {
+            return Ok(()); // interrupted, the caller will try again
+        }
+        if fds[1].revents & (POLLIN | POLLHUP) == 0 {
+            return Ok(()); // only X events
+        }
+
+        let mut buf = [0u8; 64];
+        let finished =
+            unsafe { read(stream.wake, buf.as_mut_ptr() as *mut c_void, buf.len()) } == 0;
+        stream.pending.store(false, Ordering::Release);
+        let mut lines = Vec::new();
+        for line in stream.lines.try_iter() {
+            match line {
+                Ok(l) => lines.push(l),
+                Err(e) => return Die::stderr(format!("Could not read from stdin: {}", e)),
+            }
+        }
+        if finished {
+            self.stream = None;
+        }
+
+        if !lines.is_empty() {
+            let items = format_lines(self, lines)?;
+            self.items.as_mut().unwrap().extend(items);
+            self.draw()?;
+        }
+        Ok(())
+    }
+}
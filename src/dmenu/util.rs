@@ -18,9 +18,14 @@ pub fn readstdin(drw: &mut Drw) -> CompResult<Vec<Item>> {
             Err(e) => return Die::stderr(format!("Could not read from stdin: {}", e)),
         }
     }
+    format_lines(drw, lines)
+}
+
+/// Applies the `format_stdin` hook to lines from stdin, and turns them into items
+pub fn format_lines(drw: &mut Drw, lines: Vec<String>) -> CompResult<Vec<Item>> {
     let mut ret = Vec::new();
     for line in drw.format_stdin(lines)?.into_iter() {
-        let item = Item::new(line, false, drw)?;
+        let item = Item::from_line(line, drw)?;
         if item.width as i32 > drw.pseudo_globals.inputw {
             drw.pseudo_globals.inputw = item.width as i32;
         }
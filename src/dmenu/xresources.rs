@@ -0,0 +1,157 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+use x11::xlib::*;
+
+use crate::clapflags::CLAP_FLAGS;
+use crate::config::{Clrs::*, Config, Schemes::*};
+use crate::result::*;
+
+/// Colors which can be set as `dmenu.<resource>`, with the flag which overrides each
+const COLORS: [(&str, &str, usize, usize); 15] = [
+    (
+        "normbg",
+        "color_normal_background",
+        SchemeNorm as usize,
+        ColBg as usize,
+    ),
+    (
+        "normfg",
+        "color_normal_foreground",
+        SchemeNorm as usize,
+        ColFg as usize,
+    ),
+    (
+        "selbg",
+        "color_selected_background",
+        SchemeSel as usize,
+        ColBg as usize,
+    ),
+    (
+        "selfg",
+        "color_selected_foreground",
+        SchemeSel as usize,
+        ColFg as usize,
+    ),
+    (
+        "outbg",
+        "color_out_background",
+        SchemeOut as usize,
+        ColBg as usize,
+    ),
+    (
+        "outfg",
+        "color_out_foreground",
+        SchemeOut as usize,
+        ColFg as usize,
+    ),
+    (
+        "highbg",
+        "color_highlight_background",
+        SchemeHighlight as usize,
+        ColBg as usize,
+    ),
+    (
+        "highfg",
+        "color_highlight_foreground",
+        SchemeHighlight as usize,
+        ColFg as usize,
+    ),
+    (
+        "promptbg",
+        "color_prompt_background",
+        SchemePrompt as usize,
+        ColBg as usize,
+    ),
+    (
+        "promptfg",
+        "color_prompt_foreground",
+        SchemePrompt as usize,
+        ColFg as usize,
+    ),
+    (
+        "counterbg",
+        "color_counter_background",
+        SchemeCounter as usize,
+        ColBg as usize,
+    ),
+    (
+        "counterfg",
+        "color_counter_foreground",
+        SchemeCounter as usize,
+        ColFg as usize,
+    ),
+    (
+        "scrollbar",
+        "scrollbar_color",
+        SchemeScrollbar as usize,
+        ColFg as usize,
+    ),
+    (
+        "scrollbartrack",
+        "scrollbar_track_color",
+        SchemeScrollbar as usize,
+        ColBg as usize,
+    ),
+    (
+        "border",
+        "border_color",
+        SchemeBorder as usize,
+        ColBg as usize,
+    ),
+];
+
+unsafe fn get(db: XrmDatabase, resource: &str) -> Option<String> {
+    let name = CString::new(format!("dmenu.{}", resource)).ok()?;
+    let class = CString::new(format!("Dmenu.{}", resource)).ok()?;
+    let mut kind = ptr::null_mut();
+    let mut value = XrmValue {
+        size: 0,
+        addr: ptr::null_mut(),
+    };
+    if XrmGetResource(db, name.as_ptr(), class.as_ptr(), &mut kind, &mut value) == 0
+        || value.addr.is_null()
+    {
+        return None;
+    }
+    Some(
+        CStr::from_ptr(value.addr)
+            .to_string_lossy()
+            .trim()
+            .to_owned(),
+    )
+}
+
+/// Applies `dmenu.font`, `dmenu.normbg` and friends from the X resource database.
+/// Must run after the flags are parsed, as any flag given takes priority.
+pub unsafe fn load(dpy: *mut Display, config: &mut Config) -> CompResult<()> {
+    let resources = XResourceManagerString(dpy);
+    if resources.is_null() {
+        return Ok(()); // no xrdb loaded
+    }
+    XrmInitialize();
+    let db = XrmGetStringDatabase(resources);
+    if db.is_null() {
+        return Ok(());
+    }
+    let result = apply(db, config);
+    XrmDestroyDatabase(db);
+    result
+}
+
+unsafe fn apply(db: XrmDatabase, config: &mut Config) -> CompResult<()> {
+    if CLAP_FLAGS.occurrences_of("font") == 0 {
+        if let Some(font) = get(db, "font") {
+            config.fontstrings.insert(0, font);
+        }
+    }
+
+    for (resource, flag, scheme, col) in COLORS.iter() {
+        if CLAP_FLAGS.occurrences_of(flag) > 0 {
+            continue;
+        }
+        if let Some(color) = get(db, resource) {
+            config.colors[*scheme][*col] = color;
+        }
+    }
+    Ok(())
+}
@@ -5,31 +5,62 @@ use crate::clapflags::CLAP_FLAGS;
 
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
+use rayon::slice::ParallelSliceMut;
+use std::cmp::Reverse;
 
+use crate::config::MatchMode;
 use crate::drw::Drw;
 use crate::item::Item;
+use crate::normalize::fold_str;
 use crate::result::*;
 
+// stdin index, and the item with its sort key
+type Ranked = (usize, (Item, i64, Reverse<u64>));
+
 #[override_flag(flag = nofuzz, invert = true)]
 impl Drw {
     pub fn gen_matches(&mut self) -> CompResult<Vec<Item>> {
-	let searchterm = self.input.clone();
-	let matcher: Box<dyn FuzzyMatcher> = Box::new(SkimMatcherV2::default());
-	let mut items: Vec<(Item, i64)> = 
-	    self.get_items().iter().map(|item| {
-		(item.clone(),
-		 if let Some(score) = matcher.fuzzy_match(&item.text, &searchterm) {
-		     -score
-		 } else {
-		     1
-		 })
-	    }).collect();
-	items.retain(|(_, score)| *score <= 0);
-	if searchterm.len() > 0 {
-	    items.sort_by_key(|(item, _)| item.text.len()); // this prioritizes exact matches
-	    items.sort_by_key(|(_, score)| *score);
+	if self.config.match_mode != MatchMode::Substring {
+	    return self.match_by_mode(); // fuzzy only replaces substring matching
+	}
+	let searchterm = if self.config.normalize {
+	    fold_str(&self.input)
+	} else {
+	    self.input.clone()
+	};
+	let matcher = if !self.config.case_sensitive && !self.config.smart_case {
+	    SkimMatcherV2::default().ignore_case()
+	} else {
+	    SkimMatcherV2::default().smart_case()
+	};
+	let threshold = self.config.parallel_threshold;
+	let history = self.history.as_ref();
+	let candidates = self.items.as_ref().unwrap().candidates(&searchterm);
+	let mut items: Vec<Ranked> =
+	    self.items.as_ref().unwrap().filter_candidates(candidates, threshold, |item| {
+		matcher.fuzzy_indices(item.match_text(), &searchterm).map(|(score, indices)| {
+		    let frecency = history.map_or(0, |history| history.frecency(&item.output));
+		    let mut item = item.clone();
+		    item.highlight_chars(&indices);
+		    (item, -score, Reverse(frecency))
+		})
+	    });
+	// anything matching a longer query also matches this one, so cache it
+	self.items.as_mut().unwrap().cache_matches(
+	    searchterm.clone(), items.iter().map(|(index, _)| *index).collect());
+	if !searchterm.is_empty() || history.is_some() {
+	    // best score first, then most used, then shortest (this prioritizes exact matches),
+	    // then stdin order
+	    let key = |(_, (item, score, frecency)): &Ranked| {
+		(*score, *frecency, if searchterm.is_empty() { 0 } else { item.search.len() })
+	    };
+	    if items.len() >= threshold {
+		items.par_sort_by_key(key);
+	    } else {
+		items.sort_by_key(key);
+	    }
 	}
 
-	Ok(items.into_iter().map(|(item, _)| item).collect())
+	Ok(items.into_iter().map(|(_, (item, _, _))| item).collect())
     }
 }